@@ -0,0 +1,126 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    io, iter,
+};
+
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use crate::{
+    files::{
+        io::{SyncHandle, SyncTask},
+        sources,
+        verify::{self, VerifyStatus},
+        walk::Walker,
+        Dirs, Source,
+    },
+    metadata::{assets::AssetIndex, game::VersionInfo, manifest::VersionsManifest},
+    resources::DEFAULT_MANIFEST_URL,
+    tasks::{Manager, State},
+};
+
+const LIMIT: usize = 16;
+
+#[derive(Debug)]
+pub enum BlockingError {
+    // Including being called from within a runtime
+    Runtime(io::Error),
+    Io(io::Error),
+    UnknownVersion(String),
+    // Inspected through the handles' errors
+    Failed(Vec<SyncHandle>),
+}
+
+impl Display for BlockingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Runtime(e) => write!(f, "runtime error: {e}"),
+            Self::Io(e) => write!(f, "io error: {e}"),
+            Self::UnknownVersion(id) => write!(f, "unknown version: {id}"),
+            Self::Failed(handles) => write!(f, "{} sources failed to sync", handles.len()),
+        }
+    }
+}
+
+impl Error for BlockingError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Runtime(e) | Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+// Blocking on a worker of another runtime would panic or stall it
+fn runtime() -> Result<Runtime, BlockingError> {
+    if Handle::try_current().is_ok() {
+        return Err(BlockingError::Runtime(io::Error::other(
+            "blocking api called from within a runtime",
+        )));
+    }
+    Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(BlockingError::Runtime)
+}
+
+fn finished(handle: SyncHandle) -> Result<SyncHandle, BlockingError> {
+    match handle.state() {
+        State::Finished => Ok(handle),
+        _ => Err(BlockingError::Failed(vec![handle])),
+    }
+}
+
+pub fn fetch_manifest(dirs: &Dirs) -> Result<VersionsManifest, BlockingError> {
+    let source = sources::manifest(DEFAULT_MANIFEST_URL).expect("invalid manifest url");
+    runtime()?.block_on(async {
+        let mut manager = Manager::default();
+        let handle = manager.new_task(SyncTask::new(source, dirs));
+        manager.wait_all().await;
+        let handle = finished(handle)?;
+        let manifest = handle
+            .result()
+            .and_then(|result| result.as_ref().ok())
+            .and_then(|output| output.downcast_ref::<VersionsManifest>())
+            .cloned();
+        Ok(manifest.expect("manifest task returned something else"))
+    })
+}
+
+// Syncs every file of the version, natives aren't extracted
+pub fn install(version_id: &str, dirs: &Dirs) -> Result<(), BlockingError> {
+    let manifest = fetch_manifest(dirs)?;
+    let version = manifest
+        .version(version_id)
+        .ok_or_else(|| BlockingError::UnknownVersion(version_id.to_owned()))?;
+    runtime()?.block_on(async {
+        let mut walker = Walker::new(Manager::default().with_limit(LIMIT), dirs);
+        walker.extend(iter::once(version));
+        let mut failed = Vec::new();
+        while let Some(handle) = walker.next().await {
+            if !matches!(handle.state(), State::Finished) {
+                failed.push(handle);
+            }
+        }
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(BlockingError::Failed(failed))
+        }
+    })
+}
+
+pub fn verify<'a>(
+    dirs: &Dirs,
+    version_info: &'a VersionInfo,
+    asset_index: Option<&'a AssetIndex>,
+) -> Result<Vec<(Source<'a>, VerifyStatus)>, BlockingError> {
+    runtime()?
+        .block_on(verify::verify_instance(
+            dirs,
+            version_info,
+            asset_index,
+            LIMIT,
+        ))
+        .map_err(BlockingError::Io)
+}
@@ -0,0 +1,129 @@
+use std::{sync::OnceLock, time::Duration};
+
+use reqwest::{Client, Proxy};
+use url::Url;
+
+pub const DEFAULT_USER_AGENT: &str = concat!("mcl-rs/", env!("CARGO_PKG_VERSION"));
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DEFAULT_MAX_IDLE_PER_HOST: usize = 32;
+// Applied per chunk rather than per request, so big files on slow links aren't cut off
+pub const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    // socks5:// proxies require the `socks` feature
+    pub proxy: Option<Url>,
+    pub connect_timeout: Option<Duration>,
+    pub user_agent: Option<String>,
+    // Idle connections kept for reuse, should be at least the manager's limit
+    pub max_idle_per_host: Option<usize>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            connect_timeout: Some(DEFAULT_CONNECT_TIMEOUT),
+            user_agent: Some(DEFAULT_USER_AGENT.to_owned()),
+            max_idle_per_host: Some(DEFAULT_MAX_IDLE_PER_HOST),
+        }
+    }
+}
+
+impl ClientConfig {
+    pub fn with_proxy(self, proxy: Url) -> Self {
+        Self {
+            proxy: Some(proxy),
+            ..self
+        }
+    }
+
+    pub fn with_connect_timeout(self, timeout: Duration) -> Self {
+        Self {
+            connect_timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    pub fn with_user_agent(self, user_agent: impl Into<String>) -> Self {
+        Self {
+            user_agent: Some(user_agent.into()),
+            ..self
+        }
+    }
+
+    pub fn with_max_idle_per_host(self, max_idle_per_host: usize) -> Self {
+        Self {
+            max_idle_per_host: Some(max_idle_per_host),
+            ..self
+        }
+    }
+
+    pub fn build(&self) -> reqwest::Result<Client> {
+        // Thousands of small assets are multiplexed over few connections when h2 is negotiated
+        let mut builder = Client::builder().http2_adaptive_window(true);
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(Proxy::all(proxy.as_str())?);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        if let Some(max_idle_per_host) = self.max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max_idle_per_host);
+        }
+        builder.build()
+    }
+}
+
+// Shared by tasks that weren't given a client explicitly, so they reuse pooled connections
+// instead of doing a TCP and TLS handshake per file. A custom client should be shared the same
+// way, i.e. given once to the walker
+pub fn default_client() -> Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT
+        .get_or_init(|| {
+            ClientConfig::default()
+                .build()
+                .expect("can't build default client")
+        })
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{serve, Response};
+
+    use super::*;
+
+    fn echo_user_agent() -> Url {
+        serve(|request| Response::ok(request.header("user-agent").unwrap_or_default()))
+    }
+
+    #[tokio::test]
+    async fn default_user_agent_is_sent() {
+        let url = echo_user_agent();
+        let body = default_client()
+            .get(url)
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert_eq!(body, DEFAULT_USER_AGENT);
+    }
+
+    #[tokio::test]
+    async fn custom_user_agent_is_sent() {
+        let url = echo_user_agent();
+        let client = ClientConfig::default()
+            .with_user_agent("launcher/1.0")
+            .build()
+            .unwrap();
+        let body = client.get(url).send().await.unwrap().text().await.unwrap();
+        assert_eq!(body, "launcher/1.0");
+    }
+}
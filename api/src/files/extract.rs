@@ -0,0 +1,364 @@
+use std::{
+    fs,
+    io::{self, ErrorKind, Read, Seek},
+    iter,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use tokio::task::{self, JoinSet};
+use tracing::{instrument, trace, warn};
+use zip::ZipArchive;
+
+use crate::tasks::CancelToken;
+
+const PROGRESS_CHUNK: usize = 64 * 1024;
+
+// Names come from untrusted archives and indexes, so they must never point outside of the dir
+pub fn enclosed_path(dir: &Path, name: &str) -> io::Result<PathBuf> {
+    let relative = Path::new(name);
+    if relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        Ok(dir.join(relative))
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("path escapes {}: {name}", dir.display()),
+        ))
+    }
+}
+
+// Excludes are dirs with or without trailing slash (or exact files), signatures are never needed
+fn is_excluded(name: &str, exclude: &[String]) -> bool {
+    iter::once("META-INF")
+        .chain(exclude.iter().map(|prefix| prefix.trim_end_matches('/')))
+        .filter(|prefix| !prefix.is_empty())
+        .any(|prefix| {
+            name.strip_prefix(prefix)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+}
+
+fn extract_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    i: usize,
+    exclude: &[String],
+    dir: &Path,
+) -> io::Result<Option<PathBuf>> {
+    let mut file = archive.by_index(i)?;
+    let name = file.name();
+    if file.is_dir() || is_excluded(name, exclude) {
+        trace!(name, "skipped entry");
+        return Ok(None);
+    }
+
+    let path = enclosed_path(dir, name)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    io::copy(&mut file, &mut fs::File::create(&path)?)?;
+    trace!(?path, "extracted entry");
+    Ok(Some(path))
+}
+
+#[instrument(skip(archive))]
+pub fn extract_natives<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    exclude: &[String],
+    dir: &Path,
+) -> io::Result<Vec<PathBuf>> {
+    let mut extracted = Vec::new();
+    for i in 0..archive.len() {
+        extracted.extend(extract_entry(archive, i, exclude, dir)?);
+    }
+
+    Ok(extracted)
+}
+
+// Entries are split between blocking workers, each one decompresses its own share using a clone
+// of the archive, so the reader should be cheap to clone (i.e. OwnedZipArchive)
+#[instrument(skip(archive))]
+pub async fn extract_all<R>(
+    archive: ZipArchive<R>,
+    exclude: &[String],
+    dir: &Path,
+    parallelism: usize,
+) -> io::Result<Vec<PathBuf>>
+where
+    R: Read + Seek + Clone + Send + 'static,
+{
+    let parallelism = parallelism.clamp(1, archive.len().max(1));
+    let exclude = Arc::new(exclude.to_vec());
+    let mut workers = JoinSet::new();
+    for worker in 0..parallelism {
+        let mut archive = archive.clone();
+        let exclude = Arc::clone(&exclude);
+        let dir = dir.to_owned();
+        workers.spawn_blocking(move || {
+            let mut extracted = Vec::new();
+            for i in (worker..archive.len()).step_by(parallelism) {
+                extracted.extend(extract_entry(&mut archive, i, &exclude, &dir)?);
+            }
+            io::Result::Ok(extracted)
+        });
+    }
+
+    let mut extracted = Vec::new();
+    while let Some(joined) = workers.join_next().await {
+        extracted.extend(joined.map_err(io::Error::other)??);
+    }
+    Ok(extracted)
+}
+
+fn cancelled() -> io::Error {
+    io::Error::new(ErrorKind::Interrupted, "extraction cancelled")
+}
+
+// Copied by chunks, so a single big entry still reports progress and can be cancelled
+fn extract_entry_with_progress<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    i: usize,
+    exclude: &[String],
+    dir: &Path,
+    cancel: &CancelToken,
+    mut on_chunk: impl FnMut(u64),
+) -> io::Result<Option<PathBuf>> {
+    let mut file = archive.by_index(i)?;
+    let name = file.name();
+    if file.is_dir() || is_excluded(name, exclude) {
+        trace!(name, "skipped entry");
+        return Ok(None);
+    }
+
+    let path = enclosed_path(dir, name)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut output = fs::File::create(&path)?;
+    let mut buf = vec![0; PROGRESS_CHUNK];
+    loop {
+        if cancel.is_cancelled() {
+            drop(output);
+            fs::remove_file(&path)?;
+            return Err(cancelled());
+        }
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        io::Write::write_all(&mut output, &buf[..read])?;
+        on_chunk(read as u64);
+    }
+    trace!(?path, "extracted entry");
+    Ok(Some(path))
+}
+
+// Sequential, on the blocking pool, progress is (extracted, total) uncompressed bytes of entries
+// that aren't skipped. Cancelling removes what was already extracted and fails with Interrupted
+#[instrument(skip(archive, progress))]
+pub async fn extract_with_progress<R>(
+    mut archive: ZipArchive<R>,
+    exclude: &[String],
+    dir: &Path,
+    cancel: &CancelToken,
+    mut progress: impl FnMut(u64, u64) + Send + 'static,
+) -> io::Result<Vec<PathBuf>>
+where
+    R: Read + Seek + Send + 'static,
+{
+    let exclude = exclude.to_vec();
+    let dir = dir.to_owned();
+    let cancel = cancel.clone();
+    task::spawn_blocking(move || {
+        let mut total = 0;
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            if !file.is_dir() && !is_excluded(file.name(), &exclude) {
+                total += file.size();
+            }
+        }
+        progress(0, total);
+
+        let mut extracted = Vec::new();
+        let mut done = 0;
+        for i in 0..archive.len() {
+            let entry = if cancel.is_cancelled() {
+                Err(cancelled())
+            } else {
+                extract_entry_with_progress(&mut archive, i, &exclude, &dir, &cancel, |read| {
+                    done += read;
+                    progress(done, total);
+                })
+            };
+            match entry {
+                Ok(path) => extracted.extend(path),
+                Err(e) => {
+                    if e.kind() == ErrorKind::Interrupted {
+                        for path in &extracted {
+                            if let Err(e) = fs::remove_file(path) {
+                                warn!(%e, ?path, "couldn't remove extracted entry");
+                            }
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(extracted)
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+fn marker_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!(".{id}.extracted"))
+}
+
+// Members of an archive (identified by its hash) extracted to the dir, None when any is missing
+pub fn extracted_members(dir: &Path, id: &str) -> io::Result<Option<Vec<PathBuf>>> {
+    let listing = match fs::read_to_string(marker_path(dir, id)) {
+        Ok(listing) => listing,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let members = listing
+        .lines()
+        .map(|name| enclosed_path(dir, name))
+        .collect::<io::Result<Vec<_>>>()?;
+    Ok(members.iter().all(|path| path.exists()).then_some(members))
+}
+
+// Written after extraction, so interrupted one isn't taken as complete
+pub fn mark_extracted(dir: &Path, id: &str, members: &[PathBuf]) -> io::Result<()> {
+    let listing: Vec<_> = members
+        .iter()
+        .filter_map(|path| path.strip_prefix(dir).ok())
+        .map(|name| name.to_string_lossy())
+        .collect();
+    fs::create_dir_all(dir)?;
+    fs::write(marker_path(dir, id), listing.join("\n"))
+}
+
+// Extracts the archive unless the marker says it's already done
+#[instrument(skip(archive))]
+pub async fn extract_all_once<R>(
+    archive: ZipArchive<R>,
+    exclude: &[String],
+    dir: &Path,
+    parallelism: usize,
+    id: &str,
+) -> io::Result<Vec<PathBuf>>
+where
+    R: Read + Seek + Clone + Send + 'static,
+{
+    match extracted_members(dir, id) {
+        Ok(Some(members)) => {
+            trace!("already extracted");
+            return Ok(members);
+        }
+        Ok(None) => (),
+        Err(e) => warn!(%e, "invalid extraction marker"),
+    }
+    let members = extract_all(archive, exclude, dir, parallelism).await?;
+    mark_extracted(dir, id, &members)?;
+    Ok(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::{write::FileOptions, ZipWriter};
+
+    use crate::testing::temp_dir;
+
+    use super::*;
+
+    fn archive(entries: &[(&str, &str)]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in entries {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn rejects_escaping_names() {
+        let dir = Path::new("/mc/natives");
+        for name in ["../evil.so", "lib/../../evil.so", "/etc/passwd"] {
+            let e = enclosed_path(dir, name).unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::InvalidData, "{name}");
+        }
+        assert_eq!(
+            enclosed_path(dir, "./lib/liblwjgl.so").unwrap(),
+            dir.join("./lib/liblwjgl.so")
+        );
+    }
+
+    #[test]
+    fn traversal_entries_are_not_extracted() {
+        let root = temp_dir("traversal-natives");
+        let dir = root.join("natives");
+        let mut archive = archive(&[("liblwjgl.so", "lib"), ("../evil.so", "evil")]);
+        let e = extract_natives(&mut archive, &[], &dir).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidData);
+        assert!(!root.join("evil.so").exists());
+    }
+
+    #[tokio::test]
+    async fn traversal_entries_are_not_extracted_in_parallel() {
+        let root = temp_dir("traversal-all");
+        let dir = root.join("natives");
+        let archive = archive(&[("a.so", "a"), ("b/../../evil.so", "evil"), ("c.so", "c")]);
+        let e = extract_all(archive, &[], &dir, 2).await.unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidData);
+        assert!(!root.join("evil.so").exists());
+    }
+
+    // Extract block of org.lwjgl.lwjgl:lwjgl-platform:2.9.4-nightly-20150209
+    fn lwjgl_exclude() -> Vec<String> {
+        vec!["META-INF/".to_owned()]
+    }
+
+    #[test]
+    fn excludes_are_dir_prefixes() {
+        let exclude = lwjgl_exclude();
+        assert!(is_excluded("META-INF/MANIFEST.MF", &exclude));
+        assert!(is_excluded("META-INF", &exclude));
+        assert!(!is_excluded("META-INF.txt", &exclude));
+        assert!(!is_excluded("liblwjgl64.so", &exclude));
+
+        let exclude = ["docs".to_owned(), "readme.txt".to_owned()];
+        assert!(is_excluded("docs/index.html", &exclude));
+        assert!(is_excluded("readme.txt", &exclude));
+        assert!(!is_excluded("docs2/index.html", &exclude));
+        assert!(!is_excluded("readme.txt.bak", &exclude));
+    }
+
+    #[test]
+    fn signatures_are_always_excluded() {
+        assert!(is_excluded("META-INF/MOJANGCS.SF", &[]));
+        assert!(is_excluded("META-INF/MOJANGCS.RSA", &["".to_owned()]));
+    }
+
+    #[test]
+    fn lwjgl_natives_are_extracted_without_metadata() {
+        let dir = temp_dir("lwjgl-natives");
+        let mut archive = archive(&[
+            ("META-INF/MANIFEST.MF", "Manifest-Version: 1.0"),
+            ("META-INF/MOJANGCS.SF", "signature"),
+            ("liblwjgl64.so", "lwjgl"),
+            ("libopenal64.so", "openal"),
+        ]);
+        let mut extracted = extract_natives(&mut archive, &lwjgl_exclude(), &dir).unwrap();
+        extracted.sort();
+        assert_eq!(
+            extracted,
+            [dir.join("liblwjgl64.so"), dir.join("libopenal64.so")]
+        );
+        assert!(!dir.join("META-INF").exists());
+    }
+}
@@ -1,29 +1,207 @@
 use std::{
     any::Any,
-    fmt::Debug,
+    collections::HashSet,
+    error::Error,
+    fmt::{self, Debug, Display},
     future::Future,
     io::{self, Cursor},
     path::{Path, PathBuf},
     pin::Pin,
-    sync::atomic::{AtomicU64, Ordering},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
-use reqwest::Client;
+use chrono::{DateTime, Utc};
+use crossbeam_utils::atomic::AtomicCell;
+use reqwest::{header, Client, Response, StatusCode};
 use serde::de::DeserializeOwned;
-use tokio::fs::{self, create_dir_all};
-use tracing::{info_span, instrument, trace, Instrument};
+use serde_derive::{Deserialize, Serialize};
+use sha1_smol::Sha1;
+use tokio::{
+    fs::{self, create_dir_all},
+    io::{AsyncWrite, AsyncWriteExt, BufWriter},
+    task, time,
+};
+use tracing::{info_span, instrument, trace, warn, Instrument};
 use url::Url;
-use zip::ZipArchive;
+use zip::{result::ZipError, ZipArchive};
 
 use crate::{
+    client::{default_client, DEFAULT_READ_TIMEOUT},
     metadata::{assets::AssetIndex, game::VersionInfo, manifest::VersionsManifest},
-    tasks::{GenerateTask, Handle},
+    tasks::{Clock, GenerateTask, Handle, RateLimiter, State},
 };
 
-use super::{ContentType, Dirs, Source};
+use super::{
+    extract,
+    mirror::UrlRewriter,
+    verify::{verify_file, VerifyStatus},
+    ContentType, Dirs, LinkStrategy, ProcessingHint, Source,
+};
 
 type PinBoxFut<R> = Pin<Box<dyn Future<Output = R> + Send + Sync + 'static>>;
-type OwnedZipArchive = ZipArchive<Cursor<Vec<u8>>>;
+// Cheap to clone, so entries can be read in parallel
+pub type OwnedZipArchive = ZipArchive<Cursor<Arc<[u8]>>>;
+// Including the first one
+const RETRY_AFTER_ATTEMPTS: usize = 3;
+
+pub type SyncHandle = Handle<SyncTask, <SyncTask as GenerateTask>::Output>;
+
+#[derive(Debug)]
+pub struct HttpError {
+    pub status: StatusCode,
+    pub url: Url,
+    pub body: String,
+    // Delay asked by the server when rate limited or overloaded
+    pub retry_after: Option<Duration>,
+}
+
+impl HttpError {
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self.status,
+            StatusCode::TOO_MANY_REQUESTS
+                | StatusCode::SERVICE_UNAVAILABLE
+                | StatusCode::BAD_GATEWAY
+                | StatusCode::GATEWAY_TIMEOUT
+        )
+    }
+}
+
+// Either delay in seconds or an HTTP-date, past dates mean right away
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+async fn http_error(url: &Url, response: Response) -> SyncError {
+    let retry_after = response
+        .headers()
+        .get(header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_retry_after);
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    SyncError::Http(Box::new(HttpError {
+        status,
+        url: url.clone(),
+        body,
+        retry_after,
+    }))
+}
+
+impl Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} responded with {}", self.url, self.status)
+    }
+}
+
+impl Error for HttpError {}
+
+#[derive(Debug)]
+pub enum SyncError {
+    Io(io::Error),
+    Request(reqwest::Error),
+    // Boxed, as the url alone makes it the biggest variant
+    Http(Box<HttpError>),
+    SizeMismatch {
+        expected: u64,
+        actual: u64,
+    },
+    HashMismatch {
+        expected: String,
+        actual: String,
+    },
+    UnsupportedEncoding(String),
+    Decode {
+        path: PathBuf,
+        err: serde_json::Error,
+    },
+    Zip {
+        path: PathBuf,
+        err: ZipError,
+    },
+    // Truncated or bogus index, which would leave the game without assets
+    EmptyAssetIndex(PathBuf),
+    Timeout,
+    Cancelled,
+}
+
+impl SyncError {
+    // Worth retrying, other errors will most likely happen again
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Request(e) => e.is_timeout() || e.is_connect(),
+            Self::Http(e) => e.is_transient(),
+            Self::Timeout => true,
+            _ => false,
+        }
+    }
+}
+
+impl Display for SyncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error: {e}"),
+            Self::Request(e) => write!(f, "request failed: {e}"),
+            Self::Http(e) => Display::fmt(e, f),
+            Self::SizeMismatch { expected, actual } => {
+                write!(f, "expected {expected} bytes, but content has {actual}")
+            }
+            Self::HashMismatch { expected, actual } => {
+                write!(f, "expected sha1 {expected}, but content has {actual}")
+            }
+            Self::UnsupportedEncoding(encoding) => {
+                write!(f, "unsupported content encoding: {encoding}")
+            }
+            Self::Decode { path, err } => write!(f, "invalid json in {}: {err}", path.display()),
+            Self::Zip { path, err } => {
+                write!(f, "invalid zip archive {}: {err}", path.display())
+            }
+            Self::EmptyAssetIndex(path) => {
+                write!(f, "asset index {} has no objects", path.display())
+            }
+            Self::Timeout => write!(f, "read timed out"),
+            Self::Cancelled => write!(f, "task cancelled"),
+        }
+    }
+}
+
+impl Error for SyncError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Request(e) => Some(e),
+            Self::Http(e) => Some(e.as_ref()),
+            Self::Decode { err, .. } => Some(err),
+            Self::Zip { err, .. } => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SyncError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<reqwest::Error> for SyncError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Request(e)
+    }
+}
 
 #[derive(Debug, Copy, Clone, Default)]
 pub enum Validation {
@@ -33,31 +211,266 @@ pub enum Validation {
     Usual,
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct BufferConfig {
+    // Upper bound for preallocation by the expected size, so bogus content length can't exhaust memory
+    pub max_initial_capacity: usize,
+    pub writer_capacity: usize,
+}
+
+impl Default for BufferConfig {
+    fn default() -> Self {
+        Self {
+            max_initial_capacity: 16 * 1024 * 1024,
+            writer_capacity: 64 * 1024,
+        }
+    }
+}
+
+// Shared between tasks of a single sync, so common parents (i.e. objects/ab) are created once
+#[derive(Debug, Default)]
+pub struct CreatedDirs(Mutex<HashSet<PathBuf>>);
+
+impl CreatedDirs {
+    async fn ensure(&self, dir: &Path) -> io::Result<()> {
+        if self.0.lock().expect("poisoned lock").contains(dir) {
+            return Ok(());
+        }
+        create_dir_all(dir).await?;
+        self.0.lock().expect("poisoned lock").insert(dir.to_owned());
+        Ok(())
+    }
+}
+
+// Stored next to the file, so it can be requested conditionally later
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl Validators {
+    fn of(response: &Response) -> Option<Self> {
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        let validators = Self {
+            etag: header(header::ETAG),
+            last_modified: header(header::LAST_MODIFIED),
+        };
+        (validators.etag.is_some() || validators.last_modified.is_some()).then_some(validators)
+    }
+}
+
+// Removed on drop unless persisted, so cancelled or failed tasks don't leave partial files
+#[derive(Debug)]
+struct PartFile {
+    path: PathBuf,
+    persisted: bool,
+}
+
+impl PartFile {
+    // Unique per writer, as tasks of different targets may share one in the store, so neither
+    // truncates, renames or removes the other's file
+    fn of(target: &Path) -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let mut path = target.to_owned().into_os_string();
+        path.push(format!(
+            ".{}-{}.part",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        Self {
+            path: PathBuf::from(path),
+            persisted: false,
+        }
+    }
+
+    // Created in place rather than on the blocking pool, otherwise an aborted task could remove
+    // the file before it's created and leave it behind
+    fn create(&self) -> io::Result<fs::File> {
+        std::fs::File::create(&self.path).map(fs::File::from_std)
+    }
+
+    async fn persist(mut self, to: &Path) -> io::Result<()> {
+        fs::rename(&self.path, to).await?;
+        self.persisted = true;
+        Ok(())
+    }
+}
+
+impl Drop for PartFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    warn!(%e, path = ?self.path, "couldn't remove part file")
+                }
+                _ => trace!(path = ?self.path, "removed part file"),
+            }
+        }
+    }
+}
+
+// Exponentially weighted transfer rate, sampled at most once per interval by whoever comes first
+#[derive(Debug)]
+pub struct SpeedMeter {
+    start: Instant,
+    // Start is used when not given
+    clock: Option<Arc<dyn Clock>>,
+    // Zero until the first sample, which is only a baseline
+    last_nanos: AtomicU64,
+    last_bytes: AtomicU64,
+    // f64 bits, NaN until measured
+    rate: AtomicU64,
+}
+
+impl Default for SpeedMeter {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            clock: None,
+            last_nanos: Default::default(),
+            last_bytes: Default::default(),
+            rate: AtomicU64::new(f64::NAN.to_bits()),
+        }
+    }
+}
+
+impl SpeedMeter {
+    const INTERVAL: Duration = Duration::from_millis(500);
+    const SMOOTHING: f64 = 0.3;
+
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock: Some(clock),
+            ..Default::default()
+        }
+    }
+
+    pub fn sample(&self, bytes: u64) {
+        let elapsed = match &self.clock {
+            Some(clock) => clock.elapsed(),
+            None => self.start.elapsed(),
+        };
+        let now = (elapsed.as_nanos() as u64).max(1);
+        let last = self.last_nanos.load(Ordering::Acquire);
+        // Another thread may have sampled a later time in between, which must never be undone
+        let Some(since) = now.checked_sub(last).filter(|since| *since > 0) else {
+            return;
+        };
+        if last != 0 && since < Self::INTERVAL.as_nanos() as u64 {
+            return;
+        }
+        if self
+            .last_nanos
+            .compare_exchange(last, now, Ordering::AcqRel, Ordering::Relaxed)
+            .is_err()
+        {
+            return;
+        }
+        let prev_bytes = self.last_bytes.swap(bytes, Ordering::AcqRel);
+        if last == 0 {
+            return;
+        }
+        // Progress is reset when falling back from a mirror
+        let delta = if bytes >= prev_bytes {
+            bytes - prev_bytes
+        } else {
+            bytes
+        };
+        let current = delta as f64 / Duration::from_nanos(since).as_secs_f64();
+        let prev = f64::from_bits(self.rate.load(Ordering::Acquire));
+        let rate = if prev.is_nan() {
+            current
+        } else {
+            prev + Self::SMOOTHING * (current - prev)
+        };
+        self.rate.store(rate.to_bits(), Ordering::Release);
+    }
+
+    // Bytes per second
+    pub fn rate(&self) -> Option<f64> {
+        Some(f64::from_bits(self.rate.load(Ordering::Acquire))).filter(|rate| !rate.is_nan())
+    }
+}
+
 // TODO : try to generify w/ lifetime for source, not to cloning some data
 // Currently impossible, because Manager::new_task awaits M: 'static
 #[derive(Debug)]
 pub struct SyncTask {
     client: Client,
+    read_timeout: Duration,
+    max_retry_after: Option<Duration>,
+    buffers: BufferConfig,
     progress: AtomicU64,
+    speed: SpeedMeter,
+    rewriter: Option<Arc<dyn UrlRewriter>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    created_dirs: Option<Arc<CreatedDirs>>,
 
     url: Url,
     path: PathBuf,
+    copy_from: Option<PathBuf>,
     validation: Validation,
     r#type: ContentType,
     size: Option<u64>,
+    hash: Option<String>,
+    discover_size: bool,
+    discovered_size: AtomicCell<Option<u64>>,
+    strict_indexes: bool,
+    extracted_to: Option<PathBuf>,
+    // Where the content is really kept and how it's linked to path
+    store: Option<(PathBuf, LinkStrategy)>,
 }
 
 impl SyncTask {
     pub fn new(source: Source<'_>, dirs: &Dirs) -> Self {
+        let path = source.local_path(dirs);
+        let shared_store = dirs
+            .shared_store
+            .as_ref()
+            .filter(|_| source.r#type.processing_hint() == ProcessingHint::Raw);
+        let task = Self::new_at(source, path);
+        let store = shared_store
+            .zip(task.hash.as_deref())
+            .and_then(|(store, hash)| Some((store.path_for(hash)?, store.strategy)));
+        Self { store, ..task }
+    }
+
+    // Explicit target, e.g. a staging dir or an exported artifact
+    pub fn new_at(source: Source<'_>, path: PathBuf) -> Self {
         Self {
-            path: source.local_path(dirs),
+            path,
             size: source.size,
+            // Placeholders of missing hashes (i.e. 00null) can't be checked
+            hash: source
+                .hash
+                .filter(|hash| hash.len() == 40 && hash.bytes().all(|b| b.is_ascii_hexdigit()))
+                .map(str::to_owned),
             r#type: source.r#type,
             url: source.url.into_owned(),
 
-            client: Default::default(),
+            copy_from: Default::default(),
+            client: default_client(),
+            read_timeout: DEFAULT_READ_TIMEOUT,
+            max_retry_after: Default::default(),
+            buffers: Default::default(),
             progress: Default::default(),
+            speed: Default::default(),
+            rewriter: Default::default(),
+            rate_limiter: Default::default(),
+            created_dirs: Default::default(),
             validation: Default::default(),
+            discover_size: Default::default(),
+            discovered_size: Default::default(),
+            strict_indexes: Default::default(),
+            extracted_to: Default::default(),
+            store: Default::default(),
         }
     }
 
@@ -65,10 +478,120 @@ impl SyncTask {
         Self { client, ..self }
     }
 
+    pub fn with_buffers(self, buffers: BufferConfig) -> Self {
+        Self { buffers, ..self }
+    }
+
+    pub fn with_read_timeout(self, read_timeout: Duration) -> Self {
+        Self {
+            read_timeout,
+            ..self
+        }
+    }
+
+    // Transient responses with Retry-After are retried after the asked delay, but at most this
+    pub fn with_max_retry_after(self, max_retry_after: Duration) -> Self {
+        Self {
+            max_retry_after: Some(max_retry_after),
+            ..self
+        }
+    }
+
     pub fn with_validation(self, validation: Validation) -> Self {
         Self { validation, ..self }
     }
 
+    pub fn with_rewriter(self, rewriter: Arc<dyn UrlRewriter>) -> Self {
+        Self {
+            rewriter: Some(rewriter),
+            ..self
+        }
+    }
+
+    pub fn with_rate_limiter(self, rate_limiter: Arc<RateLimiter>) -> Self {
+        Self {
+            rate_limiter: Some(rate_limiter),
+            ..self
+        }
+    }
+
+    pub fn with_created_dirs(self, created_dirs: Arc<CreatedDirs>) -> Self {
+        Self {
+            created_dirs: Some(created_dirs),
+            ..self
+        }
+    }
+
+    // Unknown size is requested with HEAD before syncing, so it can be validated and tracked
+    pub fn with_size_discovery(self, discover_size: bool) -> Self {
+        Self {
+            discover_size,
+            ..self
+        }
+    }
+
+    // Asset index without objects fails the task instead of only being warned about
+    pub fn with_strict_indexes(self, strict_indexes: bool) -> Self {
+        Self {
+            strict_indexes,
+            ..self
+        }
+    }
+
+    // Natives already extracted to the dir (by extract_all_once) are neither read nor downloaded,
+    // the task yields Vec<PathBuf> of extracted members instead of the archive then
+    pub fn with_extracted_to(self, dir: PathBuf) -> Self {
+        Self {
+            extracted_to: Some(dir),
+            ..self
+        }
+    }
+
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            speed: SpeedMeter::with_clock(clock),
+            ..self
+        }
+    }
+
+    // Local file with the same content, that is linked or copied instead of downloading
+    pub fn with_copy_from(self, copy_from: PathBuf) -> Self {
+        Self {
+            copy_from: Some(copy_from),
+            ..self
+        }
+    }
+
+    // Same target and configuration, but nothing done yet, i.e. to run a failed one again
+    pub fn to_retry(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            read_timeout: self.read_timeout,
+            max_retry_after: self.max_retry_after,
+            buffers: self.buffers,
+            progress: Default::default(),
+            speed: match &self.speed.clock {
+                Some(clock) => SpeedMeter::with_clock(Arc::clone(clock)),
+                None => Default::default(),
+            },
+            rewriter: self.rewriter.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            created_dirs: self.created_dirs.clone(),
+            url: self.url.clone(),
+            path: self.path.clone(),
+            copy_from: self.copy_from.clone(),
+            validation: self.validation,
+            r#type: self.r#type,
+            size: self.size,
+            hash: self.hash.clone(),
+            discover_size: self.discover_size,
+            discovered_size: Default::default(),
+            strict_indexes: self.strict_indexes,
+            extracted_to: self.extracted_to.clone(),
+            store: self.store.clone(),
+        }
+    }
+
     pub fn path(&self) -> &Path {
         self.path.as_path()
     }
@@ -78,7 +601,50 @@ impl SyncTask {
     }
 
     pub fn size(&self) -> Option<u64> {
-        self.size
+        self.size.or_else(|| self.discovered_size.load())
+    }
+
+    // Sampled on read too, so a stalled download slows down instead of keeping its last rate
+    pub fn speed(&self) -> Option<f64> {
+        self.speed.sample(self.progress());
+        self.speed.rate()
+    }
+
+    pub fn eta(&self) -> Option<Duration> {
+        let remaining = self.size()?.saturating_sub(self.progress());
+        eta(remaining, self.speed()?)
+    }
+
+    #[instrument]
+    // Only an extra, so the download goes on with an unknown size when it fails
+    async fn discover_size(&self) {
+        if !self.discover_size || self.size.is_some() {
+            return;
+        }
+        let content_len = self
+            .with_mirror(|url| async move {
+                let response = self.client.head(url.clone()).send().await?;
+                if !response.status().is_success() {
+                    return Err(http_error(&url, response).await);
+                }
+                // Compressed length says nothing about the file
+                if response.headers().contains_key(header::CONTENT_ENCODING) {
+                    return Ok(None);
+                }
+                // Body of HEAD response is empty, so its size hint can't be used
+                Ok(response
+                    .headers()
+                    .get(header::CONTENT_LENGTH)
+                    .and_then(|value| value.to_str().ok()?.parse().ok()))
+            })
+            .await;
+        match content_len {
+            Ok(content_len) => {
+                trace!(?content_len, "discovered size");
+                self.discovered_size.store(content_len);
+            }
+            Err(e) => warn!(%e, "couldn't discover size"),
+        }
     }
 
     #[instrument]
@@ -88,54 +654,302 @@ impl SyncTask {
             Validation::Force => Ok(false),
             Validation::Usual => match fs::metadata(&self.path).await {
                 Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
-                Ok(_) if self.size.is_none() => Ok(true),
-                Ok(metadata) => Ok(metadata.len() == self.size.unwrap()),
+                Ok(metadata) => Ok(self.size().is_none_or(|size| metadata.len() == size)),
                 Err(e) => Err(e),
             },
         }
     }
 
+    async fn create_parent(&self) -> io::Result<()> {
+        let Some(parent) = self.path.parent() else {
+            return Ok(());
+        };
+        match &self.created_dirs {
+            Some(created_dirs) => created_dirs.ensure(parent).await,
+            None => create_dir_all(parent).await,
+        }
+    }
+
+    fn part_file(&self) -> PartFile {
+        PartFile::of(&self.path)
+    }
+
+    async fn with_mirror<T, F, Fut>(&self, f: F) -> Result<T, SyncError>
+    where
+        F: Fn(Url) -> Fut,
+        Fut: Future<Output = Result<T, SyncError>>,
+    {
+        if let Some(url) = self
+            .rewriter
+            .as_ref()
+            .and_then(|rewriter| rewriter.rewrite(&self.url, self.r#type))
+        {
+            match f(url.clone()).await {
+                // Only the mirror itself being unreachable or broken is worth another try
+                Err(e @ (SyncError::Request(_) | SyncError::Http(_) | SyncError::Timeout)) => {
+                    warn!(%e, %url, "mirror failed, falling back to origin");
+                    self.progress.store(0, Ordering::Relaxed);
+                }
+                res => return res,
+            }
+        }
+        f(self.url.clone()).await
+    }
+
     #[instrument]
-    async fn download(&self) -> io::Result<Vec<u8>> {
-        let mut response = self
-            .client
-            .get(self.url.clone())
+    async fn request(
+        &self,
+        url: &Url,
+        validators: Option<&Validators>,
+    ) -> Result<Response, SyncError> {
+        let mut attempt = 1;
+        loop {
+            let result = self.request_once(url, validators).await;
+            let delay = match (&result, self.max_retry_after) {
+                (Err(SyncError::Http(e)), Some(max))
+                    if e.is_transient() && attempt < RETRY_AFTER_ATTEMPTS =>
+                {
+                    e.retry_after.map(|delay| delay.min(max))
+                }
+                _ => None,
+            };
+            let Some(delay) = delay else {
+                return result;
+            };
+            warn!(%url, ?delay, attempt, "retrying when asked by the server");
+            match &self.speed.clock {
+                Some(clock) => clock.sleep(delay).await,
+                None => time::sleep(delay).await,
+            }
+            attempt += 1;
+        }
+    }
+
+    async fn request_once(
+        &self,
+        url: &Url,
+        validators: Option<&Validators>,
+    ) -> Result<Response, SyncError> {
+        let mut request = self.client.get(url.clone());
+        if let Some(validators) = validators {
+            if let Some(etag) = &validators.etag {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &validators.last_modified {
+                request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let response = request
             .send()
             .instrument(info_span!("wait_for_response"))
-            .await
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            .await?;
 
-        match (self.size, response.content_length()) {
-            (Some(source_len), Some(content_len)) if source_len != content_len => {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "source and content sizes mismatch",
-                ));
+        let status = response.status();
+        if status == StatusCode::NOT_MODIFIED && validators.is_some() {
+            return Ok(response);
+        }
+        if !status.is_success() {
+            return Err(http_error(url, response).await);
+        }
+
+        // Supported encodings are decoded by the client, which also drops the compressed length.
+        // zstd isn't one of them in reqwest 0.11, so it's never asked for and rejected if sent
+        if let Some(encoding) = response.headers().get(header::CONTENT_ENCODING) {
+            return Err(SyncError::UnsupportedEncoding(
+                String::from_utf8_lossy(encoding.as_bytes()).into_owned(),
+            ));
+        }
+        match (self.size(), response.content_length()) {
+            (Some(expected), Some(actual)) if expected != actual => {
+                Err(SyncError::SizeMismatch { expected, actual })
             }
-            _ => (),
+            _ => Ok(response),
         }
+    }
 
-        let buf = async {
-            let buf_size = self.size.or(response.content_length()).unwrap_or_default();
-            let mut buf = Vec::with_capacity(buf_size as usize);
-            trace!(buf_size, "allocated buf");
-            while let Some(chunk) = response
-                .chunk()
+    #[instrument(skip(response, writer, handle))]
+    async fn copy_chunks<W>(
+        &self,
+        mut response: Response,
+        writer: &mut W,
+        handle: &SyncHandle,
+    ) -> Result<(), SyncError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut written = 0;
+        // Hashed along the way, so content isn't read twice
+        let mut hasher = self.hash.as_ref().map(|_| Sha1::new());
+        while handle.checkpoint().await {
+            let Some(chunk) = time::timeout(self.read_timeout, response.chunk())
                 .in_current_span()
                 .await
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
-            {
-                let len = chunk.len();
-                buf.extend_from_slice(chunk.as_ref());
-                self.progress.fetch_add(len as u64, Ordering::Relaxed);
+                .map_err(|_| SyncError::Timeout)??
+            else {
+                writer.flush().await?;
+                // Decoded content has no length to be prechecked
+                if let Some(expected) = self.size().filter(|expected| *expected != written) {
+                    return Err(SyncError::SizeMismatch {
+                        expected,
+                        actual: written,
+                    });
+                }
+                return match (&self.hash, hasher) {
+                    (Some(expected), Some(hasher)) => {
+                        let actual = hasher.digest().to_string();
+                        if actual.eq_ignore_ascii_case(expected) {
+                            Ok(())
+                        } else {
+                            Err(SyncError::HashMismatch {
+                                expected: expected.clone(),
+                                actual,
+                            })
+                        }
+                    }
+                    _ => Ok(()),
+                };
+            };
+            let len = chunk.len();
+            written += len as u64;
+            // Broken mirrors may send more than expected, which is caught before writing
+            if let Some(expected) = self.size().filter(|expected| written > *expected) {
+                return Err(SyncError::SizeMismatch {
+                    expected,
+                    actual: written,
+                });
+            }
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.acquire(len as u64).await;
+            }
+            if let Some(hasher) = &mut hasher {
+                hasher.update(&chunk);
             }
+            writer.write_all(chunk.as_ref()).await?;
+            let progress = self.progress.fetch_add(len as u64, Ordering::Relaxed) + len as u64;
+            self.speed.sample(progress);
+        }
+        // Response is dropped here, so the connection is released
+        Err(SyncError::Cancelled)
+    }
 
-            io::Result::Ok(buf)
+    #[instrument(skip(handle))]
+    async fn download(&self, handle: &SyncHandle) -> Result<Vec<u8>, SyncError> {
+        self.with_mirror(|url| async move {
+            let response = self.request(&url, None).await?;
+            let buf_size = self
+                .size()
+                .or(response.content_length())
+                .unwrap_or_default()
+                .min(self.buffers.max_initial_capacity as u64);
+            let mut buf = Vec::with_capacity(buf_size as usize);
+            trace!(buf_size, "allocated buf");
+            self.copy_chunks(response, &mut buf, handle)
+                .instrument(info_span!("fetch_data"))
+                .await?;
+            Ok(buf)
+        })
+        .await
+    }
+
+    #[instrument]
+    async fn copy_local(&self, from: &Path) -> io::Result<bool> {
+        // The first one may be unfinished or stale, so it's only reused once it has the content
+        let (path, size, hash) = (from.to_owned(), self.size(), self.hash.clone());
+        let status = task::spawn_blocking(move || verify_file(&path, size, hash.as_deref()))
+            .await
+            .map_err(io::Error::other)??;
+        if status != VerifyStatus::Ok {
+            trace!(?status, "not reusing local copy");
+            return Ok(false);
+        }
+        let part_file = self.part_file();
+        if std::fs::hard_link(from, &part_file.path).is_err() {
+            trace!("hard link failed, copying");
+            let mut reader = fs::File::open(from).await?;
+            tokio::io::copy(&mut reader, &mut part_file.create()?).await?;
+        }
+        part_file.persist(&self.path).await?;
+        Ok(true)
+    }
+
+    // Streams directly into a file, so large files aren't kept in memory
+    #[instrument(skip(handle))]
+    async fn download_to_file(&self, handle: &SyncHandle) -> Result<(), SyncError> {
+        self.create_parent().await?;
+        if let Some(from) = &self.copy_from {
+            if self.copy_local(from).await? {
+                return Ok(());
+            }
         }
-        .instrument(info_span!("fetch_data"))
+        let Some((store_path, strategy)) = &self.store else {
+            return self.stream_to(&self.path, handle).await;
+        };
+        // Forcing refetches the stored copy too, i.e. after it was changed through a hardlink
+        match fs::metadata(store_path).await {
+            Ok(metadata)
+                if !matches!(self.validation, Validation::Force)
+                    && self.size().is_none_or(|size| metadata.len() == size) =>
+            {
+                trace!(?store_path, "found in shared store")
+            }
+            Ok(_) => self.stream_to(store_path, handle).await?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                if let Some(parent) = store_path.parent() {
+                    create_dir_all(parent).await?;
+                }
+                self.stream_to(store_path, handle).await?
+            }
+            Err(e) => return Err(e.into()),
+        }
+        self.link_from_store(store_path, *strategy).await?;
+        Ok(())
+    }
+
+    async fn stream_to(&self, path: &Path, handle: &SyncHandle) -> Result<(), SyncError> {
+        let part_file = PartFile::of(path);
+        self.with_mirror(|url| {
+            let part_file = &part_file;
+            async move {
+                let response = self.request(&url, None).await?;
+                // Small assets don't need the whole buffer
+                let capacity = match self.size() {
+                    Some(size) => self.buffers.writer_capacity.min(size as usize),
+                    None => self.buffers.writer_capacity,
+                };
+                let mut writer = BufWriter::with_capacity(capacity, part_file.create()?);
+                self.copy_chunks(response, &mut writer, handle)
+                    .instrument(info_span!("stream_data"))
+                    .await
+            }
+        })
         .await?;
+        Ok(part_file.persist(path).await?)
+    }
 
-        Ok(buf)
+    // Linked next to the target and renamed over it, so an old file (possibly a link itself)
+    // is replaced rather than written through
+    async fn link_from_store(&self, from: &Path, strategy: LinkStrategy) -> io::Result<()> {
+        let part_file = self.part_file();
+        let linked = match strategy {
+            LinkStrategy::Hardlink => std::fs::hard_link(from, &part_file.path),
+            #[cfg(unix)]
+            LinkStrategy::Symlink => std::path::absolute(from)
+                .and_then(|from| std::os::unix::fs::symlink(from, &part_file.path)),
+            #[cfg(windows)]
+            LinkStrategy::Symlink => std::path::absolute(from)
+                .and_then(|from| std::os::windows::fs::symlink_file(from, &part_file.path)),
+            #[cfg(not(any(unix, windows)))]
+            LinkStrategy::Symlink => Err(io::ErrorKind::Unsupported.into()),
+            LinkStrategy::Copy => Err(io::ErrorKind::Unsupported.into()),
+        };
+        if let Err(e) = linked {
+            if strategy != LinkStrategy::Copy {
+                trace!(%e, ?strategy, "link failed, copying");
+            }
+            let mut reader = fs::File::open(from).await?;
+            tokio::io::copy(&mut reader, &mut part_file.create()?).await?;
+        }
+        part_file.persist(&self.path).await
     }
 
     #[instrument]
@@ -143,72 +957,569 @@ impl SyncTask {
         fs::read(&self.path).await
     }
 
+    #[instrument(skip(handle))]
+    async fn fetch(&self, is_valid: bool, handle: &SyncHandle) -> Result<Vec<u8>, SyncError> {
+        if is_valid {
+            let buf = self.read_local().await?;
+            // Parsed files are small, so the local copy is checked before being reused
+            match &self.hash {
+                Some(hash)
+                    if !Sha1::from(&buf)
+                        .digest()
+                        .to_string()
+                        .eq_ignore_ascii_case(hash) =>
+                {
+                    trace!("hash mismatch, downloading again")
+                }
+                _ => return Ok(buf),
+            }
+        }
+        let buf = self.download(handle).await?;
+        self.write_to_file(&buf).await?;
+        Ok(buf)
+    }
+
+    fn validators_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".validators");
+        PathBuf::from(path)
+    }
+
+    // Local copy is revalidated using ETag/Last-Modified instead of being trusted as is,
+    // because unsized files (i.e. manifest) change over time
+    #[instrument(skip(handle))]
+    async fn fetch_conditional(
+        &self,
+        is_valid: bool,
+        handle: &SyncHandle,
+    ) -> Result<Vec<u8>, SyncError> {
+        if is_valid && !matches!(self.validation, Validation::Usual) {
+            return Ok(self.read_local().await?);
+        }
+        let cached = if is_valid {
+            fs::read(self.validators_path())
+                .await
+                .ok()
+                .and_then(|buf| serde_json::from_slice::<Validators>(&buf).ok())
+        } else {
+            None
+        };
+        let fetched = self
+            .with_mirror(|url| {
+                let cached = cached.as_ref();
+                async move {
+                    let response = self.request(&url, cached).await?;
+                    if response.status() == StatusCode::NOT_MODIFIED {
+                        return Ok(None);
+                    }
+                    let validators = Validators::of(&response);
+                    let mut buf = Vec::new();
+                    self.copy_chunks(response, &mut buf, handle)
+                        .instrument(info_span!("fetch_data"))
+                        .await?;
+                    Ok(Some((buf, validators)))
+                }
+            })
+            .await?;
+        let Some((buf, validators)) = fetched else {
+            trace!("not modified, using local copy");
+            return Ok(self.read_local().await?);
+        };
+
+        self.write_to_file(&buf).await?;
+        let validators_path = self.validators_path();
+        match validators {
+            Some(validators) => {
+                let json = serde_json::to_vec(&validators).map_err(io::Error::from)?;
+                fs::write(&validators_path, json).await?;
+            }
+            None => match fs::remove_file(&validators_path).await {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                _ => (),
+            },
+        }
+        Ok(buf)
+    }
+
     #[instrument(skip(buf))]
-    fn deserialize_json<T: DeserializeOwned>(&self, buf: &[u8]) -> io::Result<T> {
-        serde_json::from_slice(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    fn deserialize_json<T: DeserializeOwned>(&self, buf: &[u8]) -> Result<T, SyncError> {
+        serde_json::from_slice(buf).map_err(|err| SyncError::Decode {
+            path: self.path.clone(),
+            err,
+        })
     }
 
     #[instrument(skip(buf))]
     async fn write_to_file(&self, buf: &[u8]) -> io::Result<()> {
-        if let Some(parent) = self.path.parent() {
-            create_dir_all(parent).await?;
-        }
-        fs::write(&self.path, buf).await
+        self.create_parent().await?;
+        // Partially written file must never be visible under the final path
+        let part_file = self.part_file();
+        let mut file = part_file.create()?;
+        file.write_all(buf).await?;
+        file.flush().await?;
+        drop(file);
+        part_file.persist(&self.path).await
     }
 
     #[instrument(skip(buf))]
-    fn read_zip(&self, buf: Vec<u8>) -> io::Result<OwnedZipArchive> {
-        // TODO : error
-        ZipArchive::new(Cursor::new(buf)).map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    fn read_zip(&self, buf: Vec<u8>) -> Result<OwnedZipArchive, SyncError> {
+        ZipArchive::new(Cursor::new(Arc::from(buf))).map_err(|err| SyncError::Zip {
+            path: self.path.clone(),
+            err,
+        })
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct SyncProgress {
+    pub completed_bytes: u64,
+    pub total_bytes: u64,
+    pub completed_files: usize,
+    pub failed_files: usize,
+    pub total_files: usize,
+    // Not included into bytes, so the ratio stays meaningful
+    pub unsized_files: usize,
+    // Sum of running tasks' rates
+    pub bytes_per_sec: u64,
+}
+
+// Stalled transfers only decay towards zero, so anything below a byte per second is unknown
+fn eta(remaining: u64, bytes_per_sec: f64) -> Option<Duration> {
+    if bytes_per_sec < 1.0 {
+        return None;
+    }
+    Duration::try_from_secs_f64(remaining as f64 / bytes_per_sec).ok()
+}
+
+impl SyncProgress {
+    pub fn of<'a>(handles: impl IntoIterator<Item = &'a SyncHandle>) -> Self {
+        handles
+            .into_iter()
+            .fold(Self::default(), |mut progress, handle| {
+                let task = handle.metadata();
+                let state = handle.state();
+                let finished = matches!(state, State::Finished);
+                progress.total_files += 1;
+                match state {
+                    State::Finished => progress.completed_files += 1,
+                    State::Failed => progress.failed_files += 1,
+                    State::Running => {
+                        progress.bytes_per_sec += task.speed().unwrap_or_default() as u64
+                    }
+                    _ => (),
+                }
+                match task.size() {
+                    Some(size) => {
+                        progress.total_bytes += size;
+                        // Valid local files are finished without any progress
+                        progress.completed_bytes += if finished {
+                            size
+                        } else {
+                            task.progress().min(size)
+                        };
+                    }
+                    None => progress.unsized_files += 1,
+                }
+                progress
+            })
+    }
+
+    pub fn ratio(&self) -> Option<f64> {
+        (self.total_bytes != 0).then(|| self.completed_bytes as f64 / self.total_bytes as f64)
+    }
+
+    // Unsized files aren't accounted
+    pub fn eta(&self) -> Option<Duration> {
+        eta(
+            self.total_bytes.saturating_sub(self.completed_bytes),
+            self.bytes_per_sec as f64,
+        )
     }
 }
 
 impl GenerateTask for SyncTask {
-    type Output = io::Result<Box<dyn Any + Send + Sync + 'static>>;
+    type Output = Result<Box<dyn Any + Send + Sync + 'static>, SyncError>;
     type Future = PinBoxFut<Self::Output>;
 
+    fn name(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn progress(&self) -> u64 {
+        SyncTask::progress(self)
+    }
+
+    fn size(&self) -> Option<u64> {
+        SyncTask::size(self)
+    }
+
+    // Task's own limiter is kept
+    fn attach_rate_limiter(&mut self, rate_limiter: &Arc<RateLimiter>) {
+        self.rate_limiter
+            .get_or_insert_with(|| Arc::clone(rate_limiter));
+    }
+
     fn task(handle: Handle<Self, Self::Output>) -> Self::Future {
         Box::pin(
             async move {
                 let metadata = handle.metadata();
+                metadata.discover_size().await;
                 let is_valid = metadata.is_valid().await?;
-                if let ty @ (ContentType::AssetIndex
-                | ContentType::VersionInfo
-                | ContentType::NativeLibrary
-                | ContentType::VersionManifest) = metadata.r#type
-                {
-                    let bytes = if is_valid {
-                        metadata.read_local().await?
-                    } else {
-                        let buf = metadata.download().await?;
-                        metadata.write_to_file(&buf).await?;
-                        buf
-                    };
-
-                    match ty {
-                        ContentType::AssetIndex => Self::Output::Ok(Box::new(
-                            metadata.deserialize_json::<AssetIndex>(&bytes)?,
-                        )),
-                        ContentType::VersionInfo => Self::Output::Ok(Box::new(
+                match metadata.r#type {
+                    ContentType::AssetIndex => {
+                        let bytes = metadata.fetch(is_valid, &handle).await?;
+                        let index = metadata.deserialize_json::<AssetIndex>(&bytes)?;
+                        if index.objects.is_empty() {
+                            if metadata.strict_indexes {
+                                return Err(SyncError::EmptyAssetIndex(metadata.path.clone()));
+                            }
+                            warn!(path = ?metadata.path, "asset index has no objects");
+                        }
+                        Self::Output::Ok(Box::new(index))
+                    }
+                    ContentType::VersionInfo => {
+                        let bytes = metadata.fetch(is_valid, &handle).await?;
+                        Self::Output::Ok(Box::new(
                             metadata.deserialize_json::<VersionInfo>(&bytes)?,
-                        )),
-                        ContentType::VersionManifest => Self::Output::Ok(Box::new(
+                        ))
+                    }
+                    ContentType::VersionManifest => {
+                        let bytes = metadata.fetch_conditional(is_valid, &handle).await?;
+                        Self::Output::Ok(Box::new(
                             metadata.deserialize_json::<VersionsManifest>(&bytes)?,
-                        )),
-                        ContentType::NativeLibrary => {
-                            Self::Output::Ok(Box::new(metadata.read_zip(bytes)?))
+                        ))
+                    }
+                    ContentType::NativeLibrary => {
+                        if let (true, Some(dir), Some(hash)) =
+                            (is_valid, &metadata.extracted_to, &metadata.hash)
+                        {
+                            if let Some(members) = extract::extracted_members(dir, hash)? {
+                                trace!(?dir, "natives already extracted");
+                                return Self::Output::Ok(Box::new(members));
+                            }
                         }
-                        _ => unreachable!(),
+                        let bytes = metadata.fetch(is_valid, &handle).await?;
+                        Self::Output::Ok(Box::new(metadata.read_zip(bytes)?))
                     }
-                } else {
-                    if !is_valid {
-                        let buf = metadata.download().await?;
-                        metadata.write_to_file(&buf).await?;
+                    ContentType::LegacyAsset
+                    | ContentType::Asset
+                    | ContentType::Library
+                    | ContentType::ClientJar
+                    | ContentType::ClientMappings
+                    | ContentType::ServerJar
+                    | ContentType::ServerMappings
+                    | ContentType::LogConfig
+                    | ContentType::Custom(_) => {
+                        if !is_valid {
+                            metadata.download_to_file(&handle).await?;
+                        }
+                        Self::Output::Ok(Box::new(()))
                     }
-                    Self::Output::Ok(Box::new(()))
                 }
             }
             .in_current_span(),
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, io::Write, sync::atomic::AtomicUsize, time::Instant};
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use crate::{
+        tasks::{Manager, ManualClock},
+        testing::{serve, temp_dir, Response},
+    };
+
+    use super::*;
+
+    fn source(url: &Url, size: Option<u64>) -> Source<'static> {
+        Source {
+            url: Cow::Owned(url.clone()),
+            name: Cow::Borrowed("file"),
+            r#type: ContentType::Custom("test"),
+            hash: None,
+            size,
+        }
+    }
+
+    #[test]
+    fn speed_is_smoothed_over_intervals() {
+        let clock = Arc::new(ManualClock::default());
+        let meter = SpeedMeter::with_clock(clock.clone());
+        // Zero time is taken for no sample at all
+        clock.advance(Duration::from_secs(1));
+        meter.sample(0);
+        assert_eq!(meter.rate(), None);
+
+        clock.advance(Duration::from_secs(1));
+        meter.sample(1000);
+        assert_eq!(meter.rate(), Some(1000.0));
+
+        // Too soon to be measured
+        clock.advance(Duration::from_millis(100));
+        meter.sample(5000);
+        assert_eq!(meter.rate(), Some(1000.0));
+
+        clock.advance(Duration::from_secs(1));
+        meter.sample(3200);
+        let rate = meter.rate().unwrap();
+        assert!((rate - 1300.0).abs() < 1e-6, "{rate}");
+    }
+
+    #[test]
+    fn eta_of_remaining_bytes() {
+        let url = Url::parse("https://example.com/file").unwrap();
+        let clock = Arc::new(ManualClock::default());
+        let task = SyncTask::new_at(source(&url, Some(10_000)), PathBuf::from("file"))
+            .with_clock(clock.clone());
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(task.eta(), None);
+
+        clock.advance(Duration::from_secs(1));
+        task.progress.store(1000, Ordering::Relaxed);
+        assert_eq!(task.eta(), Some(Duration::from_secs(9)));
+    }
+
+    #[tokio::test]
+    async fn bandwidth_is_limited() {
+        let url = serve(|_| Response::ok(vec![0; 300_000]));
+        let dir = temp_dir("bandwidth");
+        let mut manager = Manager::default().with_bandwidth_limit(100_000);
+        let start = Instant::now();
+        let handle = manager.new_task(SyncTask::new_at(
+            source(&url, Some(300_000)),
+            dir.join("file"),
+        ));
+        manager.wait_all().await;
+
+        assert_eq!(handle.state(), State::Finished);
+        // A second worth of bytes is available right away
+        assert!(start.elapsed() >= Duration::from_millis(1900));
+        assert_eq!(fs::metadata(dir.join("file")).await.unwrap().len(), 300_000);
+    }
+
+    fn part_files(dir: &Path) -> Vec<PathBuf> {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "part"))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn interrupted_writes_leave_no_file() {
+        // Connection is closed before the promised length
+        let url = serve(|_| Response::ok(vec![0; 1000]).with_header("Content-Length", "5000"));
+        let dir = temp_dir("interrupted-write");
+        let mut manager = Manager::default();
+        let handle = manager.new_task(SyncTask::new_at(source(&url, Some(5000)), dir.join("file")));
+        manager.wait_all().await;
+
+        assert_eq!(handle.state(), State::Failed);
+        assert!(!dir.join("file").exists());
+        assert_eq!(part_files(&dir), Vec::<PathBuf>::new());
+    }
+
+    #[tokio::test]
+    async fn interrupted_writes_keep_the_previous_file() {
+        let url = serve(|_| Response::ok(vec![0; 1000]).with_header("Content-Length", "5000"));
+        let dir = temp_dir("interrupted-update");
+        fs::write(dir.join("file"), "previous").await.unwrap();
+        let mut manager = Manager::default();
+        let handle = manager.new_task(
+            SyncTask::new_at(source(&url, Some(5000)), dir.join("file"))
+                .with_validation(Validation::Force),
+        );
+        manager.wait_all().await;
+
+        assert_eq!(handle.state(), State::Failed);
+        assert_eq!(fs::read(dir.join("file")).await.unwrap(), b"previous");
+        assert_eq!(part_files(&dir), Vec::<PathBuf>::new());
+    }
+
+    // Until the condition holds, the task is driven meanwhile
+    async fn until(condition: impl Fn() -> bool) {
+        while !condition() {
+            time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn paused_downloads_stop_reading() {
+        const SIZE: u64 = 64 * 1024 * 1024;
+        let url = serve(|_| Response::ok(vec![0; SIZE as usize]));
+        let dir = temp_dir("pause");
+        let mut manager = Manager::default();
+        let handle = manager.new_task(SyncTask::new_at(source(&url, Some(SIZE)), dir.join("file")));
+        until(|| handle.metadata().progress() > 0).await;
+
+        handle.pause();
+        // Chunk being read when paused is still written
+        time::sleep(Duration::from_millis(50)).await;
+        let paused_at = handle.metadata().progress();
+        time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(handle.metadata().progress(), paused_at);
+        assert!(paused_at < SIZE);
+
+        handle.resume();
+        manager.wait_all().await;
+        assert_eq!(handle.state(), State::Finished);
+        assert_eq!(handle.metadata().progress(), SIZE);
+    }
+
+    #[tokio::test]
+    async fn cancelled_downloads_leave_nothing_behind() {
+        const SIZE: u64 = 64 * 1024 * 1024;
+        let url = serve(|_| Response::ok(vec![0; SIZE as usize]));
+        let dir = temp_dir("cancel");
+        let mut manager = Manager::default();
+        let handle = manager.new_task(SyncTask::new_at(
+            source(&url, Some(SIZE)),
+            dir.join("nested/file"),
+        ));
+        until(|| handle.metadata().progress() > 0).await;
+
+        handle.cancel();
+        manager.wait_all().await;
+        assert_eq!(handle.state(), State::Cancelled);
+        assert!(!dir.join("nested/file").exists());
+        assert_eq!(part_files(&dir.join("nested")), Vec::<PathBuf>::new());
+    }
+
+    #[tokio::test]
+    async fn dropped_manager_leaves_nothing_behind() {
+        const SIZE: u64 = 64 * 1024 * 1024;
+        let url = serve(|_| Response::ok(vec![0; SIZE as usize]));
+        let dir = temp_dir("drop-manager");
+        let mut manager = Manager::default();
+        let handle = manager.new_task(SyncTask::new_at(source(&url, Some(SIZE)), dir.join("file")));
+        until(|| handle.metadata().progress() > 0).await;
+        assert_eq!(part_files(&dir).len(), 1);
+
+        drop(manager);
+        // Aborted tasks are dropped by the runtime once it gets to them
+        time::sleep(Duration::from_millis(50)).await;
+        assert!(!dir.join("file").exists());
+        assert_eq!(part_files(&dir), Vec::<PathBuf>::new());
+    }
+
+    const V1_20_1: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/data/1.20.1.json"
+    ));
+
+    #[tokio::test]
+    async fn gzip_encoded_content_is_decoded() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(V1_20_1.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_ne!(compressed.len(), V1_20_1.len());
+        let url = serve(move |_| {
+            Response::ok(compressed.clone()).with_header("Content-Encoding", "gzip")
+        });
+        let dir = temp_dir("gzip");
+        let hash = Sha1::from(V1_20_1).digest().to_string();
+        let mut manager = Manager::default();
+        let handle = manager.new_task(SyncTask::new_at(
+            Source {
+                hash: Some(&hash),
+                ..source(&url, Some(V1_20_1.len() as u64))
+            },
+            dir.join("file"),
+        ));
+        manager.wait_all().await;
+
+        assert_eq!(handle.state(), State::Finished);
+        assert_eq!(fs::read_to_string(dir.join("file")).await.unwrap(), V1_20_1);
+    }
+
+    #[tokio::test]
+    async fn unsupported_encodings_are_rejected() {
+        let url = serve(|_| Response::ok("compressed").with_header("Content-Encoding", "zstd"));
+        let dir = temp_dir("zstd");
+        let mut manager = Manager::default();
+        let handle = manager.new_task(SyncTask::new_at(source(&url, None), dir.join("file")));
+        manager.wait_all().await;
+
+        assert_eq!(handle.state(), State::Failed);
+        assert!(!dir.join("file").exists());
+    }
+
+    // Rate limited with the given Retry-After until asked the given number of times
+    fn rate_limited(retry_after: &'static str, times: usize) -> (Url, Arc<AtomicUsize>) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let url = serve({
+            let requests = Arc::clone(&requests);
+            move |_| {
+                if requests.fetch_add(1, Ordering::SeqCst) < times {
+                    Response::status(429).with_header("Retry-After", retry_after)
+                } else {
+                    Response::ok("content")
+                }
+            }
+        });
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn retries_after_the_asked_delay() {
+        let (url, requests) = rate_limited("2", 1);
+        let dir = temp_dir("retry-after");
+        let clock = Arc::new(ManualClock::default());
+        let mut manager = Manager::default();
+        let handle = manager.new_task(
+            SyncTask::new_at(source(&url, None), dir.join("file"))
+                .with_max_retry_after(Duration::from_secs(60))
+                .with_clock(clock.clone()),
+        );
+        until(|| requests.load(Ordering::SeqCst) == 1).await;
+        // Counted before the response is sent, so let the task get to its wait
+        time::sleep(Duration::from_millis(100)).await;
+
+        clock.advance(Duration::from_secs(1));
+        time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert_eq!(handle.state(), State::Running);
+
+        clock.advance(Duration::from_secs(1));
+        manager.wait_all().await;
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        assert_eq!(handle.state(), State::Finished);
+        assert_eq!(fs::read(dir.join("file")).await.unwrap(), b"content");
+    }
+
+    #[tokio::test]
+    async fn retry_delay_is_capped() {
+        let (url, requests) = rate_limited("3600", 1);
+        let dir = temp_dir("retry-after-capped");
+        let clock = Arc::new(ManualClock::default());
+        let mut manager = Manager::default();
+        let handle = manager.new_task(
+            SyncTask::new_at(source(&url, None), dir.join("file"))
+                .with_max_retry_after(Duration::from_secs(5))
+                .with_clock(clock.clone()),
+        );
+        until(|| requests.load(Ordering::SeqCst) == 1).await;
+        // Counted before the response is sent, so let the task get to its wait
+        time::sleep(Duration::from_millis(100)).await;
+
+        clock.advance(Duration::from_secs(5));
+        manager.wait_all().await;
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        assert_eq!(handle.state(), State::Finished);
+    }
+
+    #[tokio::test]
+    async fn not_retried_unless_enabled() {
+        let (url, requests) = rate_limited("2", 1);
+        let dir = temp_dir("retry-after-disabled");
+        let mut manager = Manager::default();
+        let handle = manager.new_task(SyncTask::new_at(source(&url, None), dir.join("file")));
+        manager.wait_all().await;
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert_eq!(handle.state(), State::Failed);
+    }
+}
@@ -0,0 +1,65 @@
+use std::{
+    collections::HashSet,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use tracing::{instrument, trace};
+
+use super::{ContentType, ProcessingHint, Source};
+
+// Append-only record of completed sources, so an interrupted install is resumed without
+// checking every file. Only hashed sources are recorded, as others can change remotely.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+    completed: HashSet<String>,
+}
+
+impl Journal {
+    #[instrument]
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let completed = match File::open(path) {
+            Ok(file) => BufReader::new(file).lines().collect::<io::Result<_>>()?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashSet::new(),
+            Err(e) => return Err(e),
+        };
+        trace!(len = completed.len(), "loaded journal");
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            path: path.to_owned(),
+            file,
+            completed,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Tab separated, as names never contain it
+    pub fn key(source: &Source<'_>) -> Option<String> {
+        let hash = source.hash?;
+        Some(format!("{:?}\t{hash}\t{}", source.r#type, source.name))
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.completed.contains(key)
+    }
+
+    pub fn record(&mut self, key: String) -> io::Result<()> {
+        if self.completed.contains(&key) {
+            return Ok(());
+        }
+        writeln!(self.file, "{key}")?;
+        self.completed.insert(key);
+        Ok(())
+    }
+
+    // Parsed files are needed to walk further, so they're never skipped
+    pub fn is_recordable(r#type: ContentType) -> bool {
+        r#type.processing_hint() == ProcessingHint::Raw
+    }
+}
@@ -0,0 +1,156 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use tracing::{instrument, trace};
+
+use crate::metadata::assets::{AssetIndex, AssetMetadata};
+
+use super::{extract::enclosed_path, Dirs};
+
+// Objects are either hashed ones or already laid out by legacy index sources, but never the
+// target itself
+fn object_path(
+    dirs: &Dirs,
+    name: &str,
+    object: &AssetMetadata,
+    target: &Path,
+) -> io::Result<PathBuf> {
+    let hash_path = object.object_path().ok_or_else(|| {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            format!("malformed hash: {}", object.hash),
+        )
+    })?;
+    let hashed = dirs.assets.join("objects").join(hash_path);
+    if hashed.exists() {
+        return Ok(hashed);
+    }
+    let laid_out = enclosed_path(&dirs.legacy_assets(), name)?;
+    if laid_out != target && laid_out.exists() {
+        return Ok(laid_out);
+    }
+    Err(io::Error::new(
+        ErrorKind::InvalidData,
+        format!("no object for asset {name}"),
+    ))
+}
+
+// Files present with the same size are kept, others are only replaced once their object is found
+fn lay_out(dirs: &Dirs, index: &AssetIndex, dir: &Path) -> io::Result<()> {
+    for (name, object) in &index.objects {
+        let target = enclosed_path(dir, name)?;
+        let stale = match fs::metadata(&target) {
+            Ok(metadata) if metadata.len() == object.size => continue,
+            Ok(_) => true,
+            Err(e) if e.kind() == ErrorKind::NotFound => false,
+            Err(e) => return Err(e),
+        };
+        let object_path = object_path(dirs, name, object, &target)?;
+        if stale {
+            fs::remove_file(&target)?;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if fs::hard_link(&object_path, &target).is_err() {
+            fs::copy(&object_path, &target)?;
+        }
+        trace!(?target, "linked legacy asset");
+    }
+
+    Ok(())
+}
+
+// Lays out already downloaded hashed objects by their names, e.g. when they were synced using
+// a modern index or by another launcher. Blocking
+#[instrument(skip(index))]
+pub fn build_legacy_virtual(dirs: &Dirs, index: &AssetIndex) -> io::Result<PathBuf> {
+    let virtual_dir = dirs.legacy_assets();
+    lay_out(dirs, index, &virtual_dir)?;
+
+    Ok(virtual_dir)
+}
+
+// Versions mapping assets to resources (pre-1.6 index) read them from the game dir itself.
+// Blocking, should be called after the index objects are synced
+#[instrument(skip(index))]
+pub fn build_resources(dirs: &Dirs, index: &AssetIndex, game_dir: &Path) -> io::Result<PathBuf> {
+    let resources_dir = game_dir.join("resources");
+    lay_out(dirs, index, &resources_dir)?;
+
+    Ok(resources_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::temp_dir;
+
+    use super::*;
+
+    const HASH: &str = "a9993e364706816aba3e25717850c26c9cd0d89d";
+
+    fn index(json: &str) -> AssetIndex {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn with_object(name: &str) -> Dirs {
+        let dirs = Dirs::new(temp_dir(name));
+        let object = dirs.assets.join("objects").join(&HASH[..2]).join(HASH);
+        fs::create_dir_all(object.parent().unwrap()).unwrap();
+        fs::write(object, "abc").unwrap();
+        dirs
+    }
+
+    #[test]
+    fn lays_out_legacy_index() {
+        let dirs = with_object("legacy-virtual");
+        let index = index(&format!(
+            r#"{{"virtual": true, "objects": {{"sound/a.ogg": {{"hash": "{HASH}", "size": 3}}}}}}"#
+        ));
+        let dir = build_legacy_virtual(&dirs, &index).unwrap();
+        assert_eq!(fs::read(dir.join("sound/a.ogg")).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn lays_out_pre_1_6_index_into_resources() {
+        let dirs = with_object("pre-1.6-resources");
+        let index = index(&format!(
+            r#"{{"map_to_resources": true, "objects": {{"sound/a.ogg": {{"hash": "{HASH}", "size": 3}}}}}}"#
+        ));
+        let game_dir = dirs.root.join("instance");
+        let dir = build_resources(&dirs, &index, &game_dir).unwrap();
+        assert_eq!(dir, game_dir.join("resources"));
+        assert_eq!(fs::read(dir.join("sound/a.ogg")).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn replaces_stale_files() {
+        let dirs = with_object("legacy-stale");
+        let stale = dirs.legacy_assets().join("sound/a.ogg");
+        fs::create_dir_all(stale.parent().unwrap()).unwrap();
+        fs::write(&stale, "truncated").unwrap();
+        let index = index(&format!(
+            r#"{{"virtual": true, "objects": {{"sound/a.ogg": {{"hash": "{HASH}", "size": 3}}}}}}"#
+        ));
+        build_legacy_virtual(&dirs, &index).unwrap();
+        assert_eq!(fs::read(stale).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn keeps_stale_files_without_object() {
+        let dirs = Dirs::new(temp_dir("legacy-missing"));
+        let stale = dirs.legacy_assets().join("sound/a.ogg");
+        fs::create_dir_all(stale.parent().unwrap()).unwrap();
+        fs::write(&stale, "truncated").unwrap();
+        let index = index(&format!(
+            r#"{{"virtual": true, "objects": {{"sound/a.ogg": {{"hash": "{HASH}", "size": 3}}}}}}"#
+        ));
+        let err = build_legacy_virtual(&dirs, &index).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().contains("sound/a.ogg"));
+        assert_eq!(fs::read(stale).unwrap(), b"truncated");
+    }
+}
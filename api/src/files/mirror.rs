@@ -0,0 +1,31 @@
+use std::fmt::Debug;
+
+use url::Url;
+
+use super::ContentType;
+
+pub trait UrlRewriter: Debug + Send + Sync {
+    fn rewrite(&self, original: &Url, r#type: ContentType) -> Option<Url>;
+}
+
+#[derive(Debug, Clone)]
+pub struct HostRewriter {
+    from: String,
+    to: String,
+}
+
+impl HostRewriter {
+    pub fn new(from: &Url, to: &Url) -> Self {
+        Self {
+            from: from.as_str().to_owned(),
+            to: to.as_str().to_owned(),
+        }
+    }
+}
+
+impl UrlRewriter for HostRewriter {
+    fn rewrite(&self, original: &Url, _: ContentType) -> Option<Url> {
+        let rest = original.as_str().strip_prefix(&self.from)?;
+        Url::parse(&format!("{}{rest}", self.to)).ok()
+    }
+}
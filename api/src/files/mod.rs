@@ -1,35 +1,209 @@
-use std::{borrow::Cow, path::PathBuf};
+use std::{
+    borrow::Cow,
+    fmt::Debug,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use url::Url;
 
+use crate::metadata::assets::hash_path;
+
+pub mod extract;
+pub mod journal;
+pub mod legacy;
+pub mod mirror;
 pub mod sources;
+pub mod verify;
+pub mod walk;
 // TODO : rename
 pub mod io;
 
+// Same name is synced to and put on the classpath
+const CLIENT_JAR: &str = "client.jar";
+const SERVER_JAR: &str = "server.jar";
+
 #[derive(Debug)]
 pub struct Dirs {
     pub root: PathBuf,
     pub assets: PathBuf,
     pub libraries: PathBuf,
     pub versions: PathBuf,
+    pub natives: PathBuf,
+    pub runtimes: PathBuf,
+    pub resolver: Option<Arc<dyn Resolver>>,
+    pub shared_store: Option<SharedStore>,
+}
+
+// Places kinds unknown to the crate, i.e. ContentType::Custom of mod loaders
+pub trait Resolver: Debug + Send + Sync {
+    // None falls back to root/<kind>/<name>
+    fn locate(&self, kind: &str, name: &str, dirs: &Dirs) -> Option<PathBuf>;
 }
 
 impl Default for Dirs {
     fn default() -> Self {
-        let root_dir = dirs::data_dir()
+        Self::try_default().expect("can't get root dir")
+    }
+}
+
+// Dirs may not exist yet, so space is checked for their closest existing ancestor
+fn available_space(dir: &Path) -> std::io::Result<u64> {
+    let existing = dir
+        .ancestors()
+        .find(|dir| dir.exists())
+        .unwrap_or(Path::new("."));
+    fs2::available_space(existing)
+}
+
+// Dirs may be on different volumes and it's unknown how bytes are split between them,
+// so every one of them has to fit the whole amount
+pub fn preflight(dirs: &Dirs, expected_total_bytes: u64) -> std::io::Result<()> {
+    for dir in [&dirs.assets, &dirs.libraries, &dirs.versions, &dirs.natives] {
+        let available = available_space(dir)?;
+        if available < expected_total_bytes {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::StorageFull,
+                format!(
+                    "not enough space for {}: {expected_total_bytes} bytes needed, {available} available",
+                    dir.display()
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Every dir may be anywhere, nothing assumes they share a parent
+impl Dirs {
+    // Fails when neither data nor home dir is known, i.e. HOME isn't set
+    pub fn try_default() -> std::io::Result<Self> {
+        dirs::data_dir()
             .map(|p| p.join("minecraft"))
             .or_else(|| dirs::home_dir().map(|p| p.join(".minecraft")))
-            .expect("can't get root dir");
+            .map(Self::new)
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "can't get root dir"))
+    }
+
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            assets: root.join("assets"),
+            libraries: root.join("libraries"),
+            versions: root.join("versions"),
+            natives: root.join("natives"),
+            runtimes: root.join("runtime"),
+            resolver: None,
+            shared_store: None,
+            root,
+        }
+    }
+
+    // Game data (saves, configs) is kept per instance, while downloaded files are shared
+    pub fn shared_with(instance_root: PathBuf, global_root: &Path) -> Self {
+        Self {
+            root: instance_root,
+            ..Self::new(global_root.to_owned())
+        }
+    }
+
+    pub fn with_resolver(self, resolver: Arc<dyn Resolver>) -> Self {
+        Self {
+            resolver: Some(resolver),
+            ..self
+        }
+    }
+
+    // Raw files with a known hash are kept once in the store and linked into every instance
+    pub fn with_shared_store(self, root: PathBuf, strategy: LinkStrategy) -> Self {
+        Self {
+            shared_store: Some(SharedStore { root, strategy }),
+            ..self
+        }
+    }
+
+    pub fn client_jar_path(&self, version_id: &str) -> PathBuf {
+        self.versions.join(version_id).join(CLIENT_JAR)
+    }
+
+    pub fn server_jar_path(&self, version_id: &str) -> PathBuf {
+        self.versions.join(version_id).join(SERVER_JAR)
+    }
+
+    // Named layout used by legacy asset indexes
+    pub fn legacy_assets(&self) -> PathBuf {
+        self.assets.join("virtual").join("legacy")
+    }
+}
+
+// Files are only ever replaced by renaming a new one over them, so an update of one instance
+// never touches the store. Editing a hardlinked file in place does change it for everyone though.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum LinkStrategy {
+    // Falls back to copying across filesystems
+    #[default]
+    Hardlink,
+    Copy,
+    // Falls back to copying where symlinks aren't allowed, e.g. Windows without a privilege
+    Symlink,
+}
+
+// Content addressed, laid out like hashed assets
+#[derive(Debug, Clone)]
+pub struct SharedStore {
+    pub root: PathBuf,
+    pub strategy: LinkStrategy,
+}
+
+impl SharedStore {
+    // None for malformed hashes
+    pub fn path_for(&self, hash: &str) -> Option<PathBuf> {
+        Some(self.root.join(hash_path(hash)?))
+    }
+}
+
+#[derive(Debug)]
+pub struct Hierarchy {
+    pub gamedir: PathBuf,
+    pub assets_dir: PathBuf,
+    pub libraries_dir: PathBuf,
+    pub versions_dir: PathBuf,
+    pub version_dir: PathBuf,
+    pub natives_dir: PathBuf,
+}
+
+impl Hierarchy {
+    pub fn new(dirs: &Dirs, version_id: &str) -> Self {
         Self {
-            root: root_dir.clone(),
-            assets: root_dir.join("assets"),
-            libraries: root_dir.join("libraries"),
-            versions: root_dir.join("versions"),
+            gamedir: dirs.root.clone(),
+            assets_dir: dirs.assets.clone(),
+            libraries_dir: dirs.libraries.clone(),
+            versions_dir: dirs.versions.clone(),
+            version_dir: dirs.versions.join(version_id),
+            natives_dir: dirs.natives.join(version_id),
         }
     }
+
+    // Profile's own dir for saves and mods, used as cwd and ${game_directory},
+    // while assets, libraries and natives stay where they were
+    pub fn with_game_dir(self, gamedir: PathBuf) -> Self {
+        Self { gamedir, ..self }
+    }
+
+    pub fn client_jar_path(&self) -> PathBuf {
+        self.version_dir.join(CLIENT_JAR)
+    }
+
+    // Keyed by the version synced from, as with ContentType::ServerJar
+    pub fn server_jar_path(&self, version_id: &str) -> PathBuf {
+        self.versions_dir.join(version_id).join(SERVER_JAR)
+    }
+
+    pub fn legacy_assets_dir(&self) -> PathBuf {
+        self.assets_dir.join("virtual").join("legacy")
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 pub enum ContentType {
     AssetIndex,
     LegacyAsset,
@@ -37,8 +211,44 @@ pub enum ContentType {
     Library,
     NativeLibrary,
     ClientJar,
+    ClientMappings,
+    ServerJar,
+    ServerMappings,
     VersionInfo,
     VersionManifest,
+    LogConfig,
+    // Downstream kinds, e.g. Forge installers, fetched as is
+    Custom(&'static str),
+}
+
+// What's done with fetched content before it's given to the caller
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ProcessingHint {
+    Json,
+    Zip,
+    Raw,
+}
+
+impl ContentType {
+    pub fn processing_hint(self) -> ProcessingHint {
+        match self {
+            Self::AssetIndex | Self::VersionInfo | Self::VersionManifest => ProcessingHint::Json,
+            Self::NativeLibrary => ProcessingHint::Zip,
+            Self::LegacyAsset
+            | Self::Asset
+            | Self::Library
+            | Self::ClientJar
+            | Self::ClientMappings
+            | Self::ServerJar
+            | Self::ServerMappings
+            | Self::LogConfig
+            | Self::Custom(_) => ProcessingHint::Raw,
+        }
+    }
+
+    pub fn is_metadata(self) -> bool {
+        self.processing_hint() == ProcessingHint::Json
+    }
 }
 
 #[derive(Debug)]
@@ -56,13 +266,31 @@ impl Source<'_> {
         match self.r#type {
             ContentType::AssetIndex => dirs.assets.join(format!("indexes/{}.json", self.name)),
             ContentType::Asset => dirs.assets.join("objects").join(self.name.as_ref()),
-            ContentType::LegacyAsset => dirs.assets.join("legacy").join(self.name.as_ref()),
+            ContentType::LegacyAsset => dirs.legacy_assets().join(self.name.as_ref()),
             ContentType::Library | ContentType::NativeLibrary => {
                 dirs.libraries.join(self.name.as_ref())
             }
-            ContentType::ClientJar => dirs.versions.join(self.name.as_ref()).join("client.jar"),
-            ContentType::VersionInfo => dirs.versions.join(self.name.as_ref()).join("info.json"),
+            ContentType::ClientJar => dirs.client_jar_path(&self.name),
+            ContentType::ClientMappings => dirs
+                .versions
+                .join(self.name.as_ref())
+                .join("client_mappings.txt"),
+            ContentType::ServerJar => dirs.server_jar_path(&self.name),
+            ContentType::ServerMappings => dirs
+                .versions
+                .join(self.name.as_ref())
+                .join("server_mappings.txt"),
+            ContentType::VersionInfo => dirs
+                .versions
+                .join(self.name.as_ref())
+                .join(format!("{}.json", self.name)),
             ContentType::VersionManifest => dirs.root.join("manifest.json"),
+            ContentType::LogConfig => dirs.assets.join("log_configs").join(self.name.as_ref()),
+            ContentType::Custom(kind) => dirs
+                .resolver
+                .as_ref()
+                .and_then(|resolver| resolver.locate(kind, &self.name, dirs))
+                .unwrap_or_else(|| dirs.root.join(kind).join(self.name.as_ref())),
         }
     }
 }
@@ -72,3 +300,158 @@ pub trait SourcesList<'a> {
 
     fn sources(self) -> Self::Iter;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_path(r#type: ContentType, name: &str, dirs: &Dirs) -> PathBuf {
+        let url = Url::parse("https://example.com/file").unwrap();
+        let source = Source {
+            url: Cow::Owned(url),
+            name: Cow::Borrowed(name),
+            r#type,
+            hash: None,
+            size: None,
+        };
+        source.local_path(dirs)
+    }
+
+    #[test]
+    fn paths_of_every_kind() {
+        let dirs = Dirs::new(PathBuf::from("/mc"));
+        let cases = [
+            (ContentType::AssetIndex, "5", "/mc/assets/indexes/5.json"),
+            (ContentType::Asset, "ab/abcd", "/mc/assets/objects/ab/abcd"),
+            (
+                ContentType::LegacyAsset,
+                "sound/a.ogg",
+                "/mc/assets/virtual/legacy/sound/a.ogg",
+            ),
+            (ContentType::Library, "a/b/c.jar", "/mc/libraries/a/b/c.jar"),
+            (
+                ContentType::NativeLibrary,
+                "a/b/c-natives-linux.jar",
+                "/mc/libraries/a/b/c-natives-linux.jar",
+            ),
+            (
+                ContentType::ClientJar,
+                "1.20.1",
+                "/mc/versions/1.20.1/client.jar",
+            ),
+            (
+                ContentType::ClientMappings,
+                "1.20.1",
+                "/mc/versions/1.20.1/client_mappings.txt",
+            ),
+            (
+                ContentType::ServerJar,
+                "1.20.1",
+                "/mc/versions/1.20.1/server.jar",
+            ),
+            (
+                ContentType::ServerMappings,
+                "1.20.1",
+                "/mc/versions/1.20.1/server_mappings.txt",
+            ),
+            (
+                ContentType::VersionInfo,
+                "1.20.1",
+                "/mc/versions/1.20.1/1.20.1.json",
+            ),
+            (
+                ContentType::VersionManifest,
+                "manifest",
+                "/mc/manifest.json",
+            ),
+            (
+                ContentType::LogConfig,
+                "client-1.12.xml",
+                "/mc/assets/log_configs/client-1.12.xml",
+            ),
+            (
+                ContentType::Custom("forge"),
+                "installer.jar",
+                "/mc/forge/installer.jar",
+            ),
+        ];
+        for (r#type, name, expected) in cases {
+            assert_eq!(
+                local_path(r#type, name, &dirs),
+                Path::new(expected),
+                "{type:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn natives_and_runtimes() {
+        let dirs = Dirs::new(PathBuf::from("/mc"));
+        assert_eq!(dirs.natives, Path::new("/mc/natives"));
+        assert_eq!(dirs.runtimes, Path::new("/mc/runtime"));
+        let hierarchy = Hierarchy::new(&dirs, "1.20.1");
+        assert_eq!(hierarchy.natives_dir, Path::new("/mc/natives/1.20.1"));
+    }
+
+    #[derive(Debug)]
+    struct Installers;
+
+    impl Resolver for Installers {
+        fn locate(&self, kind: &str, name: &str, dirs: &Dirs) -> Option<PathBuf> {
+            (kind == "forge").then(|| dirs.root.join("installers").join(name))
+        }
+    }
+
+    #[test]
+    fn custom_kinds_are_resolved() {
+        let dirs = Dirs::new(PathBuf::from("/mc")).with_resolver(Arc::new(Installers));
+        assert_eq!(
+            local_path(ContentType::Custom("forge"), "installer.jar", &dirs),
+            Path::new("/mc/installers/installer.jar")
+        );
+        assert_eq!(
+            local_path(ContentType::Custom("fabric"), "installer.jar", &dirs),
+            Path::new("/mc/fabric/installer.jar")
+        );
+    }
+
+    #[test]
+    fn instances_share_global_dirs() {
+        let dirs = Dirs::shared_with(PathBuf::from("/instances/modded"), Path::new("/global"));
+        assert_eq!(dirs.root, Path::new("/instances/modded"));
+        assert_eq!(
+            local_path(ContentType::Asset, "ab/abcd", &dirs),
+            Path::new("/global/assets/objects/ab/abcd")
+        );
+        assert_eq!(
+            local_path(ContentType::Library, "a/b/c.jar", &dirs),
+            Path::new("/global/libraries/a/b/c.jar")
+        );
+        assert_eq!(
+            local_path(ContentType::ClientJar, "1.20.1", &dirs),
+            Path::new("/global/versions/1.20.1/client.jar")
+        );
+        let hierarchy = Hierarchy::new(&dirs, "1.20.1");
+        assert_eq!(hierarchy.gamedir, Path::new("/instances/modded"));
+        assert_eq!(hierarchy.natives_dir, Path::new("/global/natives/1.20.1"));
+    }
+
+    #[test]
+    fn dirs_without_common_parent() {
+        let dirs = Dirs {
+            natives: PathBuf::from("/tmp/natives"),
+            runtimes: PathBuf::from("/opt/java"),
+            ..Dirs::shared_with(PathBuf::from("/instances/vanilla"), Path::new("/global"))
+        };
+        assert_eq!(
+            local_path(ContentType::AssetIndex, "5", &dirs),
+            Path::new("/global/assets/indexes/5.json")
+        );
+        assert_eq!(
+            local_path(ContentType::Custom("forge"), "installer.jar", &dirs),
+            Path::new("/instances/vanilla/forge/installer.jar")
+        );
+        let hierarchy = Hierarchy::new(&dirs, "1.20.1");
+        assert_eq!(hierarchy.natives_dir, Path::new("/tmp/natives/1.20.1"));
+    }
+}
@@ -1,23 +1,27 @@
-use std::{borrow::Cow, iter};
+use std::{borrow::Cow, collections::HashMap, path::Path};
 
 use reqwest::IntoUrl;
+use url::Url;
 
-use crate::metadata::{
-    assets::{AssetIndex, AssetMetadata},
-    game::VersionInfo,
-    manifest::Version,
+use crate::{
+    launch::features::FeatureSet,
+    metadata::{
+        assets::AssetIndex,
+        game::{build_library_path, VersionInfo},
+        manifest::Version,
+    },
 };
 
-use super::{ContentType, Source, SourcesList};
+use super::{extract::enclosed_path, ContentType, Source, SourcesList};
 
-pub fn manifest(url: impl IntoUrl) -> Source<'static> {
-    Source {
+pub fn manifest(url: impl IntoUrl) -> reqwest::Result<Source<'static>> {
+    Ok(Source {
         r#type: ContentType::VersionManifest,
-        url: Cow::Owned(url.into_url().expect("invalid manifest url")),
+        url: Cow::Owned(url.into_url()?),
         name: Cow::Borrowed("manifest"),
         hash: None,
         size: None,
-    }
+    })
 }
 
 impl<'manifest, I> SourcesList<'manifest> for I
@@ -32,7 +36,7 @@ where
             r#type: ContentType::VersionInfo,
             url: Cow::Borrowed(&version.url),
             name: Cow::Borrowed(&version.id),
-            hash: None,
+            hash: version.sha1.as_deref(),
             size: None,
         }))
     }
@@ -43,84 +47,209 @@ impl<'index> SourcesList<'index> for &'index AssetIndex {
     type Iter = Box<dyn Iterator<Item = Source<'index>> + 'index>;
 
     fn sources(self) -> Self::Iter {
-        let is_legacy = self.map_to_resources.unwrap_or(false);
-        Box::new(
-            self.objects
-                .iter()
-                .map(move |(path, AssetMetadata { hash, size })| {
-                    let hash_path = format!("{}/{}", &hash[..2], &hash);
-                    Source {
-                        url: Cow::Owned(
-                            self.origin
-                                .join(&hash_path)
-                                .expect("invalid url-encoded hash"),
-                        ),
-                        r#type: if is_legacy {
-                            ContentType::LegacyAsset
-                        } else {
-                            ContentType::Asset
-                        },
-                        name: if is_legacy {
-                            Cow::Borrowed(path)
-                        } else {
-                            Cow::Owned(hash_path)
-                        },
-                        hash: Some(hash),
-                        size: Some(*size),
-                    }
-                }),
-        )
+        let is_legacy = self.is_legacy();
+        Box::new(self.objects.iter().filter_map(move |(path, object)| {
+            let hash_path = object.object_path()?;
+            // Legacy names become paths, so ones escaping the assets dir are skipped
+            if is_legacy && enclosed_path(Path::new(""), path).is_err() {
+                return None;
+            }
+            Some(Source {
+                url: Cow::Owned(self.origin.join(&hash_path).ok()?),
+                r#type: if is_legacy {
+                    ContentType::LegacyAsset
+                } else {
+                    ContentType::Asset
+                },
+                name: if is_legacy {
+                    Cow::Borrowed(path)
+                } else {
+                    Cow::Owned(hash_path)
+                },
+                hash: Some(&object.hash),
+                size: Some(object.size),
+            })
+        }))
     }
 }
 
+// Rules of libraries are evaluated with the features, so that synced ones are the ones put on the
+// classpath by a launch with the same FeatureSet
+#[derive(Debug, Clone, Copy)]
+pub struct WithFeatures<'info>(pub &'info VersionInfo, pub &'info FeatureSet);
+
 impl<'info> SourcesList<'info> for &'info VersionInfo {
     // impl traits not allowed here for now
     type Iter = Box<dyn Iterator<Item = Source<'info>> + 'info>;
 
     fn sources(self) -> Self::Iter {
-        let asset_index = iter::once(Source {
-            r#type: ContentType::AssetIndex,
-            url: Cow::Borrowed(&self.asset_index.resource.url),
-            name: Cow::Borrowed(&self.asset_index.id),
-            hash: Some(&self.asset_index.resource.sha1),
-            size: Some(self.asset_index.resource.size),
-        });
-        let client_jar = iter::once(Source {
-            r#type: ContentType::ClientJar,
-            url: Cow::Borrowed(&self.downloads.client.url),
-            name: Cow::Borrowed(&self.id),
-            hash: Some(&self.downloads.client.sha1),
-            size: Some(self.downloads.client.size),
-        });
-        let libraries = self
-            .libraries
-            .iter()
-            .filter(|lib| lib.is_supported_by_rules())
-            .filter_map(|lib| lib.resources.artifact.as_ref())
-            .map(|artifact| Source {
+        version_sources(self, FeatureSet::default().to_map())
+    }
+}
+
+impl<'info> SourcesList<'info> for WithFeatures<'info> {
+    type Iter = Box<dyn Iterator<Item = Source<'info>> + 'info>;
+
+    fn sources(self) -> Self::Iter {
+        version_sources(self.0, self.1.to_map())
+    }
+}
+
+fn version_sources<'info>(
+    version_info: &'info VersionInfo,
+    features: HashMap<&'static str, bool>,
+) -> Box<dyn Iterator<Item = Source<'info>> + 'info> {
+    let native_features = features.clone();
+    let asset_index = version_info.asset_index.iter().map(|asset_index| Source {
+        r#type: ContentType::AssetIndex,
+        url: Cow::Borrowed(&asset_index.resource.url),
+        name: Cow::Borrowed(&asset_index.id),
+        hash: Some(&asset_index.resource.sha1),
+        size: Some(asset_index.resource.size),
+    });
+    let client_jar = version_info.downloads.iter().map(|downloads| Source {
+        r#type: ContentType::ClientJar,
+        url: Cow::Borrowed(&downloads.client.url),
+        name: Cow::Borrowed(&version_info.id),
+        hash: Some(&downloads.client.sha1),
+        size: Some(downloads.client.size),
+    });
+    let libraries = version_info
+        .libraries
+        .iter()
+        .filter(move |lib| lib.is_supported_with(&features))
+        .filter_map(|lib| match (&lib.resources.artifact, &lib.url) {
+            (Some(artifact), _) => Some(Source {
                 r#type: ContentType::Library,
                 url: Cow::Borrowed(&artifact.resource.url),
                 name: Cow::Borrowed(&artifact.path),
                 hash: Some(&artifact.resource.sha1),
                 size: Some(artifact.resource.size),
-            });
-        let natives = self
-            .libraries
-            .iter()
-            .filter(|lib| lib.is_supported_by_rules())
-            .filter_map(|lib| lib.resources.get_native_for_os())
-            .map(|artifact| Source {
-                r#type: ContentType::NativeLibrary,
-                url: Cow::Borrowed(&artifact.resource.url),
-                name: Cow::Borrowed(&artifact.path),
-                hash: Some(&artifact.resource.sha1),
-                size: Some(artifact.resource.size),
-            });
+            }),
+            (None, Some(repository)) => {
+                let path = build_library_path(&lib.name, None)?;
+                Some(Source {
+                    r#type: ContentType::Library,
+                    url: Cow::Owned(repository.join(&path).ok()?),
+                    name: Cow::Owned(path),
+                    hash: None,
+                    size: None,
+                })
+            }
+            (None, None) => None,
+        });
+    let natives = version_info
+        .libraries
+        .iter()
+        .filter(move |lib| lib.is_supported_with(&native_features))
+        .filter_map(|lib| lib.resources.get_native_for_os())
+        .map(|artifact| Source {
+            r#type: ContentType::NativeLibrary,
+            url: Cow::Borrowed(&artifact.resource.url),
+            name: Cow::Borrowed(&artifact.path),
+            hash: Some(&artifact.resource.sha1),
+            size: Some(artifact.resource.size),
+        });
+    let log_config = version_info.logging.iter().map(|logging| Source {
+        r#type: ContentType::LogConfig,
+        url: Cow::Borrowed(&logging.client.config.resource.url),
+        name: Cow::Borrowed(&logging.client.config.id),
+        hash: Some(&logging.client.config.resource.sha1),
+        size: Some(logging.client.config.resource.size),
+    });
+    Box::new(
+        asset_index
+            .chain(client_jar)
+            .chain(libraries)
+            .chain(natives)
+            .chain(log_config),
+    )
+}
+
+// ProGuard mappings are only needed for deobfuscation, so they aren't a part of VersionInfo sources
+#[derive(Debug, Clone, Copy)]
+pub struct Mappings<'info>(pub &'info VersionInfo);
+
+impl<'info> SourcesList<'info> for Mappings<'info> {
+    type Iter = Box<dyn Iterator<Item = Source<'info>> + 'info>;
+
+    fn sources(self) -> Self::Iter {
+        let version_info = self.0;
+        Box::new(version_info.downloads.iter().flat_map(move |downloads| {
+            [
+                (ContentType::ClientMappings, &downloads.client_mappings),
+                (ContentType::ServerMappings, &downloads.server_mappings),
+            ]
+            .into_iter()
+            .filter_map(move |(r#type, resource)| {
+                resource.as_ref().map(|resource| Source {
+                    r#type,
+                    url: Cow::Borrowed(&resource.url),
+                    name: Cow::Borrowed(&version_info.id),
+                    hash: Some(&resource.sha1),
+                    size: Some(resource.size),
+                })
+            })
+        }))
+    }
+}
+
+// Dedicated server jar, it isn't needed to play so isn't a part of VersionInfo sources either
+#[derive(Debug, Clone, Copy)]
+pub struct Server<'info>(pub &'info VersionInfo);
+
+impl<'info> SourcesList<'info> for Server<'info> {
+    type Iter = Box<dyn Iterator<Item = Source<'info>> + 'info>;
+
+    fn sources(self) -> Self::Iter {
+        let version_info = self.0;
         Box::new(
-            asset_index
-                .chain(client_jar)
-                .chain(libraries)
-                .chain(natives),
+            version_info
+                .downloads
+                .iter()
+                .filter_map(|downloads| downloads.server.as_ref())
+                .map(|server| Source {
+                    r#type: ContentType::ServerJar,
+                    url: Cow::Borrowed(&server.url),
+                    name: Cow::Borrowed(&version_info.id),
+                    hash: Some(&server.sha1),
+                    size: Some(server.size),
+                }),
+        )
+    }
+}
+
+// Same path over https, None when it's not plain http or the path would change
+fn upgrade_to_https(url: &Url) -> Option<Url> {
+    if url.scheme() != "http" {
+        return None;
+    }
+    let mut upgraded = url.clone();
+    upgraded.set_scheme("https").ok()?;
+    if upgraded.port() == Some(80) {
+        upgraded.set_port(None).ok()?;
+    }
+    (upgraded.host() == url.host() && upgraded.path() == url.path()).then_some(upgraded)
+}
+
+// Asset objects fetched over https, as the CDN serves both, for proxies blocking plain http
+#[derive(Debug, Clone, Copy)]
+pub struct HttpsAssets<'index>(pub &'index AssetIndex);
+
+impl<'index> SourcesList<'index> for HttpsAssets<'index> {
+    type Iter = Box<dyn Iterator<Item = Source<'index>> + 'index>;
+
+    fn sources(self) -> Self::Iter {
+        Box::new(
+            self.0
+                .sources()
+                .map(|source| match upgrade_to_https(&source.url) {
+                    Some(url) => Source {
+                        url: Cow::Owned(url),
+                        ..source
+                    },
+                    None => source,
+                }),
         )
     }
 }
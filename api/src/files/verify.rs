@@ -0,0 +1,200 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use sha1_smol::Sha1;
+use tokio::task::JoinSet;
+use tracing::{instrument, trace};
+
+use crate::metadata::{assets::AssetIndex, game::VersionInfo};
+
+use super::{Dirs, Source, SourcesList};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyStatus {
+    Ok,
+    Missing,
+    SizeMismatch,
+    HashMismatch,
+}
+
+// Blocking, so better to be called on a blocking thread
+#[instrument]
+pub fn verify_file(path: &Path, size: Option<u64>, hash: Option<&str>) -> io::Result<VerifyStatus> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(VerifyStatus::Missing),
+        Err(e) => return Err(e),
+    };
+    if let Some(size) = size {
+        if file.metadata()?.len() != size {
+            return Ok(VerifyStatus::SizeMismatch);
+        }
+    }
+    let Some(hash) = hash else {
+        return Ok(VerifyStatus::Ok);
+    };
+
+    let mut hasher = Sha1::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match file.read(&mut buf)? {
+            0 => break,
+            len => hasher.update(&buf[..len]),
+        }
+    }
+    if hasher.digest().to_string().eq_ignore_ascii_case(hash) {
+        Ok(VerifyStatus::Ok)
+    } else {
+        Ok(VerifyStatus::HashMismatch)
+    }
+}
+
+#[instrument(skip(version_info, asset_index))]
+pub async fn verify_instance<'a>(
+    dirs: &Dirs,
+    version_info: &'a VersionInfo,
+    asset_index: Option<&'a AssetIndex>,
+    limit: usize,
+) -> io::Result<Vec<(Source<'a>, VerifyStatus)>> {
+    let sources: Vec<_> = version_info
+        .sources()
+        .chain(asset_index.into_iter().flat_map(SourcesList::sources))
+        .collect();
+    let mut statuses = vec![VerifyStatus::Missing; sources.len()];
+
+    let mut checks = JoinSet::new();
+    for (i, source) in sources.iter().enumerate() {
+        if checks.len() >= limit.max(1) {
+            if let Some(joined) = checks.join_next().await {
+                let (i, status) = joined.map_err(io::Error::other)??;
+                statuses[i] = status;
+            }
+        }
+        let path = source.local_path(dirs);
+        let size = source.size;
+        let hash = source.hash.map(str::to_owned);
+        checks.spawn_blocking(move || {
+            verify_file(&path, size, hash.as_deref()).map(|status| (i, status))
+        });
+    }
+    while let Some(joined) = checks.join_next().await {
+        let (i, status) = joined.map_err(io::Error::other)??;
+        statuses[i] = status;
+    }
+    trace!(total = sources.len(), "verified instance");
+
+    Ok(sources.into_iter().zip(statuses).collect())
+}
+
+// Yields only sources whose files are missing or invalid, hashing them is optional as it's slow.
+// Blocking, like verify_file. Files that can't be checked are yielded too
+pub fn missing_sources<'a, 'dirs>(
+    dirs: &'dirs Dirs,
+    sources: impl SourcesList<'a> + 'dirs,
+    check_hash: bool,
+) -> impl Iterator<Item = Source<'a>> + 'dirs
+where
+    'a: 'dirs,
+{
+    sources.sources().filter(move |source| {
+        let hash = source.hash.filter(|_| check_hash);
+        !matches!(
+            verify_file(&source.local_path(dirs), source.size, hash),
+            Ok(VerifyStatus::Ok)
+        )
+    })
+}
+
+#[derive(Debug)]
+pub struct PlanEntry<'a> {
+    pub source: Source<'a>,
+    pub path: PathBuf,
+    // None when the file couldn't be checked
+    pub status: Option<VerifyStatus>,
+}
+
+impl PlanEntry<'_> {
+    pub fn needs_download(&self) -> bool {
+        self.status != Some(VerifyStatus::Ok)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct InstallPlan<'a> {
+    pub entries: Vec<PlanEntry<'a>>,
+    pub total_bytes: u64,
+    pub download_bytes: u64,
+    // Not included into bytes
+    pub unsized_entries: usize,
+}
+
+impl<'a> InstallPlan<'a> {
+    pub fn to_download(&self) -> impl Iterator<Item = &PlanEntry<'a>> {
+        self.entries.iter().filter(|entry| entry.needs_download())
+    }
+}
+
+// Dry run of a sync, only local files are checked, sources with the same target are listed once.
+// Blocking, like verify_file
+#[instrument(skip(sources))]
+pub fn plan_install<'a>(
+    dirs: &Dirs,
+    sources: impl SourcesList<'a>,
+    check_hash: bool,
+) -> InstallPlan<'a> {
+    let mut seen = HashSet::new();
+    let mut plan = InstallPlan::default();
+    for source in sources.sources() {
+        let path = source.local_path(dirs);
+        if !seen.insert(path.clone()) {
+            continue;
+        }
+        let hash = source.hash.filter(|_| check_hash);
+        let status = verify_file(&path, source.size, hash).ok();
+        let entry = PlanEntry {
+            source,
+            path,
+            status,
+        };
+        match entry.source.size {
+            Some(size) => {
+                plan.total_bytes += size;
+                if entry.needs_download() {
+                    plan.download_bytes += size;
+                }
+            }
+            None => plan.unsized_entries += 1,
+        }
+        plan.entries.push(entry);
+    }
+    trace!(entries = plan.entries.len(), "planned install");
+
+    plan
+}
+
+// Cheap post install check without hashing, as (actual, expected) bytes. Objects sharing a hash
+// are counted once like in AssetIndex::total_size, which is what's expected, so missing or
+// truncated files make actual smaller. Blocking, like verify_file
+#[instrument(skip(index))]
+pub fn verify_assets_total(dirs: &Dirs, index: &AssetIndex) -> io::Result<(u64, u64)> {
+    let expected = index.total_size();
+    let mut actual = 0;
+    let mut seen = HashSet::new();
+    for source in index
+        .sources()
+        .filter(|source| source.hash.is_none_or(|hash| seen.insert(hash)))
+    {
+        actual += match fs::metadata(source.local_path(dirs)) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e),
+        };
+    }
+    trace!(actual, expected, "summed assets");
+
+    Ok((actual, expected))
+}
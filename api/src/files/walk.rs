@@ -0,0 +1,393 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use reqwest::Client;
+use tracing::{instrument, trace, warn};
+
+use crate::{
+    client::default_client,
+    launch::features::FeatureSet,
+    metadata::{
+        assets::{hash_path, AssetIndex},
+        game::VersionInfo,
+    },
+    tasks::{Manager, State},
+};
+
+use super::{
+    io::{BufferConfig, CreatedDirs, SyncError, SyncHandle, SyncTask, Validation},
+    journal::Journal,
+    mirror::UrlRewriter,
+    sources::{HttpsAssets, Mappings, WithFeatures},
+    ContentType, Dirs, Source, SourcesList,
+};
+
+// Order of the non-metadata sources, metadata is always fetched first as it unblocks the rest
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DownloadOrder {
+    #[default]
+    Fifo,
+    SmallestFirst,
+    LargestFirst,
+}
+
+impl DownloadOrder {
+    // Lower is started first, sources of unknown size go last
+    fn priority(self, source: &Source<'_>) -> u64 {
+        if source.r#type.is_metadata() {
+            return 0;
+        }
+        match (self, source.size) {
+            (Self::Fifo, _) => 1,
+            (Self::SmallestFirst, Some(size)) => size.saturating_add(1),
+            (Self::LargestFirst, Some(size)) => (u64::MAX - size).max(1),
+            (_, None) => u64::MAX,
+        }
+    }
+}
+
+// Spawns sources provided by finished version infos and asset indexes, so a single version info
+// source is enough to sync the whole version. Concurrency is bounded by the manager's limit.
+#[derive(Debug)]
+pub struct Walker<'dirs> {
+    manager: Manager,
+    dirs: &'dirs Dirs,
+    client: Client,
+    validation: Validation,
+    // Takes precedence over the one above, e.g. to repair only specific component
+    validation_overrides: HashMap<ContentType, Validation>,
+    buffers: BufferConfig,
+    discover_size: bool,
+    strict_indexes: bool,
+    created_dirs: Arc<CreatedDirs>,
+    rewriter: Option<Arc<dyn UrlRewriter>>,
+    mappings: bool,
+    https_assets: bool,
+    features: FeatureSet,
+    max_retry_after: Option<Duration>,
+    order: DownloadOrder,
+    journal: Option<Journal>,
+    // Journal keys of pending recordable sources by their target
+    journal_keys: HashMap<PathBuf, String>,
+
+    seen: HashSet<PathBuf>,
+    // First target for the content, used by the rest of sources with the same hash and size
+    contents: HashMap<(String, u64), PathBuf>,
+    // Tasks with their priorities, by the target they're copied from
+    waiting: HashMap<PathBuf, Vec<(SyncTask, u64)>>,
+    pending: Vec<SyncHandle>,
+}
+
+impl<'dirs> Walker<'dirs> {
+    pub fn new(manager: Manager, dirs: &'dirs Dirs) -> Self {
+        Self {
+            manager,
+            dirs,
+            client: default_client(),
+            validation: Default::default(),
+            validation_overrides: Default::default(),
+            buffers: Default::default(),
+            discover_size: Default::default(),
+            strict_indexes: Default::default(),
+            created_dirs: Default::default(),
+            rewriter: Default::default(),
+            mappings: Default::default(),
+            https_assets: Default::default(),
+            features: Default::default(),
+            max_retry_after: Default::default(),
+            order: Default::default(),
+            journal: Default::default(),
+            journal_keys: Default::default(),
+            seen: Default::default(),
+            contents: Default::default(),
+            waiting: Default::default(),
+            pending: Default::default(),
+        }
+    }
+
+    pub fn with_client(self, client: Client) -> Self {
+        Self { client, ..self }
+    }
+
+    pub fn with_validation(self, validation: Validation) -> Self {
+        Self { validation, ..self }
+    }
+
+    pub fn with_validation_for(mut self, r#type: ContentType, validation: Validation) -> Self {
+        self.validation_overrides.insert(r#type, validation);
+        self
+    }
+
+    pub fn with_buffers(self, buffers: BufferConfig) -> Self {
+        Self { buffers, ..self }
+    }
+
+    pub fn with_size_discovery(self, discover_size: bool) -> Self {
+        Self {
+            discover_size,
+            ..self
+        }
+    }
+
+    pub fn with_strict_indexes(self, strict_indexes: bool) -> Self {
+        Self {
+            strict_indexes,
+            ..self
+        }
+    }
+
+    pub fn with_rewriter(self, rewriter: Arc<dyn UrlRewriter>) -> Self {
+        Self {
+            rewriter: Some(rewriter),
+            ..self
+        }
+    }
+
+    pub fn with_mappings(self, mappings: bool) -> Self {
+        Self { mappings, ..self }
+    }
+
+    pub fn with_https_assets(self, https_assets: bool) -> Self {
+        Self {
+            https_assets,
+            ..self
+        }
+    }
+
+    // Same as the launch's, so every library put on the classpath is synced
+    pub fn with_features(self, features: FeatureSet) -> Self {
+        Self { features, ..self }
+    }
+
+    pub fn with_max_retry_after(self, max_retry_after: Duration) -> Self {
+        Self {
+            max_retry_after: Some(max_retry_after),
+            ..self
+        }
+    }
+
+    pub fn with_order(self, order: DownloadOrder) -> Self {
+        Self { order, ..self }
+    }
+
+    pub fn with_journal(self, journal: Journal) -> Self {
+        Self {
+            journal: Some(journal),
+            ..self
+        }
+    }
+
+    pub fn pending(&self) -> &[SyncHandle] {
+        &self.pending
+    }
+
+    pub fn push(&mut self, source: Source<'_>) {
+        let path = source.local_path(self.dirs);
+        if !self.seen.insert(path.clone()) {
+            trace!(?source, "already seen");
+            return;
+        }
+        let copy_from = match (source.hash, source.size) {
+            (Some(hash), Some(size)) => match self.contents.get(&(hash.to_owned(), size)) {
+                Some(first) => Some(first.clone()),
+                None => {
+                    self.contents.insert((hash.to_owned(), size), path.clone());
+                    None
+                }
+            },
+            _ => None,
+        };
+        // Waits for the first one to be finished, so the content is downloaded once
+        let waits_for = copy_from.clone().filter(|first| {
+            self.pending
+                .iter()
+                .any(|handle| handle.metadata().path() == first)
+        });
+        // Legacy assets may be laid out from the hashed objects, e.g. synced for a modern index
+        let copy_from = copy_from.or_else(|| {
+            let hash = source
+                .hash
+                .filter(|_| source.r#type == ContentType::LegacyAsset)?;
+            let hashed = self.dirs.assets.join("objects").join(hash_path(hash)?);
+            hashed.exists().then_some(hashed)
+        });
+        if let Some(journal) = &self.journal {
+            if let Some(key) =
+                Journal::key(&source).filter(|_| Journal::is_recordable(source.r#type))
+            {
+                if journal.contains(&key) {
+                    trace!(?source, "completed according to journal");
+                    return;
+                }
+                self.journal_keys.insert(path.clone(), key);
+            }
+        }
+        let priority = self.order.priority(&source);
+        let validation = self
+            .validation_overrides
+            .get(&source.r#type)
+            .copied()
+            .unwrap_or(self.validation);
+        let mut task = SyncTask::new(source, self.dirs)
+            .with_client(self.client.clone())
+            .with_validation(validation)
+            .with_buffers(self.buffers)
+            .with_size_discovery(self.discover_size)
+            .with_strict_indexes(self.strict_indexes)
+            .with_created_dirs(Arc::clone(&self.created_dirs));
+        if let Some(copy_from) = copy_from {
+            task = task.with_copy_from(copy_from);
+        }
+        if let Some(max_retry_after) = self.max_retry_after {
+            task = task.with_max_retry_after(max_retry_after);
+        }
+        if let Some(rewriter) = &self.rewriter {
+            task = task.with_rewriter(Arc::clone(rewriter));
+        }
+        match waits_for {
+            Some(first) => self
+                .waiting
+                .entry(first)
+                .or_default()
+                .push((task, priority)),
+            None => self
+                .pending
+                .push(self.manager.new_task_with_priority(task, priority)),
+        }
+    }
+
+    // Once the first one is done, whether it failed or not, as the rest may download themselves
+    fn release(&mut self, first: &Path) {
+        for (task, priority) in self.waiting.remove(first).unwrap_or_default() {
+            self.pending
+                .push(self.manager.new_task_with_priority(task, priority));
+        }
+    }
+
+    pub fn extend<'a>(&mut self, sources: impl SourcesList<'a>) {
+        for source in sources.sources() {
+            self.push(source);
+        }
+    }
+
+    fn expand(&mut self, handle: &SyncHandle) {
+        let Some(Ok(output)) = handle.result() else {
+            return;
+        };
+        if let Some(version_info) = output.downcast_ref::<VersionInfo>() {
+            let features = self.features.clone();
+            self.extend(WithFeatures(version_info, &features));
+            if self.mappings {
+                self.extend(Mappings(version_info));
+            }
+        } else if let Some(asset_index) = output.downcast_ref::<AssetIndex>() {
+            // Objects with malformed hashes or escaping names are skipped by the sources
+            let emitted = asset_index.sources().count();
+            if emitted != asset_index.objects.len() {
+                warn!(
+                    emitted,
+                    objects = asset_index.objects.len(),
+                    path = ?handle.metadata().path(),
+                    "not every asset index object is synced"
+                );
+            }
+            if self.https_assets {
+                self.extend(HttpsAssets(asset_index));
+            } else {
+                self.extend(asset_index);
+            }
+        }
+    }
+
+    fn record(&mut self, handle: &SyncHandle) {
+        // Kept for failed ones, so they're recorded once a retry succeeds
+        if matches!(handle.state(), State::Failed) {
+            return;
+        }
+        let Some(key) = self.journal_keys.remove(handle.metadata().path()) else {
+            return;
+        };
+        if let (Some(journal), State::Finished) = (&mut self.journal, handle.state()) {
+            if let Err(e) = journal.record(key) {
+                warn!(%e, journal = ?journal.path(), "couldn't record completed source");
+            }
+        }
+    }
+
+    #[instrument]
+    pub async fn next(&mut self) -> Option<SyncHandle> {
+        loop {
+            if let Some(i) = self.pending.iter().position(|handle| {
+                matches!(
+                    handle.state(),
+                    State::Finished | State::Failed | State::Cancelled
+                )
+            }) {
+                let handle = self.pending.swap_remove(i);
+                self.release(handle.metadata().path());
+                self.expand(&handle);
+                self.record(&handle);
+                return Some(handle);
+            }
+            // Exited tasks that never finished (i.e. panicked) are dropped here
+            if !self.manager.wait_next().await {
+                self.pending.clear();
+                if self.waiting.is_empty() {
+                    return None;
+                }
+                let firsts: Vec<_> = self.waiting.keys().cloned().collect();
+                for first in firsts {
+                    self.release(&first);
+                }
+            }
+        }
+    }
+
+    // Runs every pending task and whatever they expand to, returning the failed ones
+    #[instrument]
+    pub async fn finish(&mut self) -> Vec<SyncHandle> {
+        let mut failed = Vec::new();
+        while let Some(handle) = self.next().await {
+            if matches!(handle.state(), State::Failed) {
+                failed.push(handle);
+            }
+        }
+        failed
+    }
+
+    // Runs the failed tasks again, up to the given attempts, and returns the ones still failing.
+    // Non-transient errors (e.g. 404 or hash mismatch) aren't retried, as they'd fail the same way
+    #[instrument(skip(failed))]
+    pub async fn retry_failed(
+        &mut self,
+        failed: impl IntoIterator<Item = SyncHandle>,
+        attempts: usize,
+    ) -> Vec<SyncHandle> {
+        let (mut retryable, mut permanent): (Vec<_>, Vec<_>) = failed
+            .into_iter()
+            .partition(|handle| handle.error().is_some_and(SyncError::is_transient));
+        for attempt in 1..=attempts {
+            if retryable.is_empty() {
+                break;
+            }
+            trace!(attempt, failed = retryable.len(), "retrying failed sources");
+            for handle in retryable.drain(..) {
+                let task = handle.metadata().to_retry();
+                self.pending.push(self.manager.new_task(task));
+            }
+            for handle in self.finish().await {
+                if handle.error().is_some_and(SyncError::is_transient) {
+                    retryable.push(handle);
+                } else {
+                    permanent.push(handle);
+                }
+            }
+        }
+        permanent.extend(retryable);
+        permanent
+    }
+}
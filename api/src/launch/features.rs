@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuickPlay {
+    Singleplayer(String),
+    Multiplayer(String),
+    Realms(String),
+}
+
+// Keys are the ones used by Mojang in rules of version arguments
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeatureSet {
+    pub demo: bool,
+    pub custom_resolution: bool,
+    pub quick_play: Option<QuickPlay>,
+    // Where the game logs quick play sessions, which is what enables the support
+    pub quick_play_path: Option<String>,
+}
+
+impl FeatureSet {
+    pub fn demo(self, demo: bool) -> Self {
+        Self { demo, ..self }
+    }
+
+    pub fn custom_resolution(self, custom_resolution: bool) -> Self {
+        Self {
+            custom_resolution,
+            ..self
+        }
+    }
+
+    pub fn quick_play(self, quick_play: QuickPlay) -> Self {
+        Self {
+            quick_play: Some(quick_play),
+            ..self
+        }
+    }
+
+    pub fn quick_play_path(self, quick_play_path: impl Into<String>) -> Self {
+        Self {
+            quick_play_path: Some(quick_play_path.into()),
+            ..self
+        }
+    }
+
+    pub fn to_map(&self) -> HashMap<&'static str, bool> {
+        let quick_play = self.quick_play.as_ref();
+        HashMap::from([
+            ("is_demo_user", self.demo),
+            ("has_custom_resolution", self.custom_resolution),
+            ("has_quick_plays_support", self.quick_play_path.is_some()),
+            (
+                "is_quick_play_singleplayer",
+                matches!(quick_play, Some(QuickPlay::Singleplayer(_))),
+            ),
+            (
+                "is_quick_play_multiplayer",
+                matches!(quick_play, Some(QuickPlay::Multiplayer(_))),
+            ),
+            (
+                "is_quick_play_realms",
+                matches!(quick_play, Some(QuickPlay::Realms(_))),
+            ),
+        ])
+    }
+
+    // Placeholders for arguments enabled by the features
+    pub(crate) fn params(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        self.quick_play
+            .iter()
+            .map(|quick_play| match quick_play {
+                QuickPlay::Singleplayer(world) => ("quickPlaySingleplayer", world.as_str()),
+                QuickPlay::Multiplayer(server) => ("quickPlayMultiplayer", server.as_str()),
+                QuickPlay::Realms(realm) => ("quickPlayRealms", realm.as_str()),
+            })
+            .chain(
+                self.quick_play_path
+                    .iter()
+                    .map(|path| ("quickPlayPath", path.as_str())),
+            )
+    }
+}
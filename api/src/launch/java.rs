@@ -0,0 +1,146 @@
+use std::{
+    collections::HashSet,
+    env, fs, iter,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use tracing::{instrument, trace};
+
+use crate::{
+    files::Dirs,
+    metadata::game::{runtime_platform, JavaVersion},
+};
+
+const JAVA_BIN: &str = if cfg!(windows) { "java.exe" } else { "java" };
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JavaInstall {
+    pub path: PathBuf,
+    pub version: String,
+    pub major_version: usize,
+    pub vendor: Option<String>,
+}
+
+// 1.8.0_381 -> 8, 17.0.8 -> 17
+fn parse_major_version(version: &str) -> Option<usize> {
+    let mut parts = version.split(|c: char| !c.is_ascii_digit());
+    match parts.next()?.parse().ok()? {
+        1 => parts.next()?.parse().ok(),
+        major => Some(major),
+    }
+}
+
+impl JavaInstall {
+    fn parse(path: PathBuf, output: &str) -> Option<Self> {
+        let property = |key: &str| {
+            output.lines().find_map(|line| {
+                let (k, v) = line.split_once('=')?;
+                (k.trim() == key).then(|| v.trim().to_owned())
+            })
+        };
+        // `java -version` prints e.g. `openjdk version "17.0.8" 2023-07-18` if properties are absent
+        let version = property("java.version").or_else(|| {
+            let line = output.lines().find(|line| line.contains("version"))?;
+            line.split('"').nth(1).map(str::to_owned)
+        })?;
+        Some(Self {
+            path,
+            major_version: parse_major_version(&version)?,
+            version,
+            vendor: property("java.vendor"),
+        })
+    }
+
+    #[instrument(level = "trace")]
+    pub fn probe(path: &Path) -> Option<Self> {
+        let output = Command::new(path)
+            .args(["-XshowSettings:properties", "-version"])
+            .output()
+            .ok()?;
+        // Version used to be printed to stderr, newer builds may print it to stdout
+        let install = Self::parse(path.to_owned(), &String::from_utf8_lossy(&output.stderr))
+            .or_else(|| Self::parse(path.to_owned(), &String::from_utf8_lossy(&output.stdout)));
+        trace!(?install, "probed java");
+        install
+    }
+}
+
+// Mojang's launcher unpacks runtimes into runtime/<component>/<platform>/<component>
+fn bundled_runtime(component: &str, dirs: &Dirs) -> Option<PathBuf> {
+    let root = dirs.runtimes.join(component);
+    let nested = runtime_platform().map(|platform| root.join(platform).join(component));
+    iter::once(root)
+        .chain(nested)
+        .flat_map(|home| {
+            [
+                home.join("bin").join(JAVA_BIN),
+                home.join("jre.bundle/Contents/Home/bin").join(JAVA_BIN),
+            ]
+        })
+        .find(|java| java.is_file())
+}
+
+fn system_candidates() -> impl Iterator<Item = PathBuf> {
+    let java_home =
+        env::var_os("JAVA_HOME").map(|home| PathBuf::from(home).join("bin").join(JAVA_BIN));
+    let path = env::var_os("PATH")
+        .map(|path| {
+            env::split_paths(&path)
+                .map(|dir| dir.join(JAVA_BIN))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    java_home
+        .into_iter()
+        .chain(path)
+        .filter(|java| java.is_file())
+}
+
+// Older or newer Java often breaks the game (or its mods), so only the exact major version is
+// picked from the system
+#[instrument(level = "trace", skip(dirs))]
+pub fn find_runtime(java_version: &JavaVersion, dirs: &Dirs) -> Option<PathBuf> {
+    if let Some(java) = bundled_runtime(&java_version.component, dirs) {
+        trace!(?java, "found bundled runtime");
+        return Some(java);
+    }
+    system_candidates().find(|java| {
+        JavaInstall::probe(java)
+            .is_some_and(|install| install.major_version == java_version.major_version)
+    })
+}
+
+// Homes of installed JVMs are usually gathered in a single directory per OS
+fn common_homes() -> Vec<PathBuf> {
+    let (roots, suffix): (&[&str], _) = match env::consts::OS {
+        "windows" => (
+            &[
+                r"C:\Program Files\Java",
+                r"C:\Program Files\Eclipse Adoptium",
+            ],
+            "bin",
+        ),
+        "macos" => (&["/Library/Java/JavaVirtualMachines"], "Contents/Home/bin"),
+        _ => (&["/usr/lib/jvm", "/usr/java"], "bin"),
+    };
+    roots
+        .iter()
+        .filter_map(|root| fs::read_dir(root).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path().join(suffix).join(JAVA_BIN))
+        .collect()
+}
+
+// Every executable is probed once, symlinks (e.g. /usr/bin/java) are resolved to dedup installs
+#[instrument(level = "trace")]
+pub fn discover() -> Vec<JavaInstall> {
+    let mut seen = HashSet::new();
+    system_candidates()
+        .chain(common_homes().into_iter().filter(|java| java.is_file()))
+        .filter_map(|java| fs::canonicalize(java).ok())
+        .filter(|java| seen.insert(java.clone()))
+        .filter_map(|java| JavaInstall::probe(&java))
+        .collect()
+}
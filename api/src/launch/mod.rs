@@ -1 +1,4 @@
-
+pub mod features;
+pub mod java;
+pub mod process;
+pub mod server;
@@ -1,31 +1,145 @@
 use std::{
     borrow::Cow,
     collections::HashMap,
-    env::{self, JoinPathsError},
+    env,
     ffi::{OsStr, OsString},
     fmt::Debug,
-    iter,
-    path::Path,
-    process::Command,
+    fs, io, iter,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
 };
 
-use tracing::{error, instrument, trace};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    process::Child,
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+};
+use tracing::{error, instrument, trace, warn};
+
+use crate::{
+    files::Hierarchy,
+    metadata::{
+        assets::AssetIndex,
+        game::{os_name, Arguments, VersionInfo},
+    },
+};
+
+use super::features::FeatureSet;
 
-use crate::{io::file::Hierarchy, metadata::game::VersionInfo};
+// Named in legacy layouts, otherwise only found by its hash through the synced index
+fn dock_icon(hierarchy: &Hierarchy, version: &VersionInfo) -> Option<PathBuf> {
+    const ICON: &str = "icons/minecraft.icns";
 
+    let id = version.assets.as_deref()?;
+    let index_path = hierarchy
+        .assets_dir
+        .join("indexes")
+        .join(format!("{id}.json"));
+    let index: AssetIndex = serde_json::from_slice(&fs::read(index_path).ok()?).ok()?;
+    let icon = if index.is_legacy() {
+        hierarchy.legacy_assets_dir().join(ICON)
+    } else {
+        hierarchy
+            .assets_dir
+            .join("objects")
+            .join(index.objects.get(ICON)?.object_path()?)
+    };
+    icon.exists().then_some(icon)
+}
+
+// Unknown placeholders are left verbatim, so new ones added by Mojang don't break anything.
+// Single pass: values are never substituted again and placeholders don't nest, i.e. the first
+// '}' closes one. $${ is a literal ${
 fn substitute_arg(arg: &str, params: &HashMap<&str, Cow<'_, OsStr>>) -> OsString {
-    if let Some(i) = arg.find("${") {
-        if let Some(j) = arg[i..].find('}') {
-            if let Some(replacement) = params.get(&arg[i + 2..i + j]) {
-                let mut output = OsString::new();
-                output.push(OsStr::new(&arg[..i]));
-                output.push(replacement);
-                output.push(OsStr::new(&arg[i + j + 1..]));
-                return output;
-            }
+    let mut output = OsString::new();
+    let mut rest = arg;
+    while let Some(i) = rest.find("${") {
+        if rest[..i].ends_with('$') {
+            output.push(&rest[..i - 1]);
+            output.push("${");
+            rest = &rest[i + 2..];
+            continue;
+        }
+        // Unclosed one is literal too
+        let Some(j) = rest[i..].find('}') else {
+            output.push(&rest[..i + 2]);
+            rest = &rest[i + 2..];
+            continue;
+        };
+        output.push(&rest[..i]);
+        match params.get(&rest[i + 2..i + j]) {
+            Some(replacement) => output.push(replacement),
+            None => output.push(&rest[i..i + j + 1]),
+        }
+        rest = &rest[i + j + 1..];
+    }
+    output.push(rest);
+    output
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Session<'a> {
+    pub username: &'a str,
+    pub uuid: &'a str,
+    pub access_token: &'a str,
+    pub xuid: &'a str,
+    pub user_type: &'a str,
+    pub client_id: &'a str,
+}
+
+impl<'a> Session<'a> {
+    pub fn offline(username: &'a str) -> Self {
+        Self {
+            username,
+            uuid: "00000000-0000-0000-0000-000000000000",
+            access_token: "0",
+            xuid: "0",
+            user_type: "legacy",
+            client_id: "0",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LaunchOptions<'a> {
+    pub session: Session<'a>,
+    pub resolution: Option<(u32, u32)>,
+    pub fullscreen: bool,
+    // Requires the log config to be downloaded
+    pub logging: bool,
+}
+
+impl<'a> LaunchOptions<'a> {
+    pub fn new(session: Session<'a>) -> Self {
+        Self {
+            session,
+            resolution: None,
+            fullscreen: false,
+            logging: false,
+        }
+    }
+
+    pub fn with_resolution(self, width: u32, height: u32) -> Self {
+        Self {
+            resolution: Some((width, height)),
+            ..self
         }
     }
-    OsString::from(arg)
+
+    pub fn with_fullscreen(self, fullscreen: bool) -> Self {
+        Self { fullscreen, ..self }
+    }
+
+    pub fn with_logging(self, logging: bool) -> Self {
+        Self { logging, ..self }
+    }
+}
+
+#[derive(Debug)]
+pub enum Entrypoint<'a> {
+    MainClass(&'a str),
+    // Self-contained jar, i.e. a dedicated server
+    Jar(PathBuf),
 }
 
 #[derive(Debug)]
@@ -33,42 +147,56 @@ pub struct GameCommand<'a> {
     pub cwd: &'a Path,
     pub jvm_args: Vec<OsString>,
     pub game_args: Vec<OsString>,
-    pub main_class: &'a str,
+    pub entrypoint: Entrypoint<'a>,
+    pub classpath: Vec<PathBuf>,
+
+    pub extra_jvm_args: Vec<OsString>,
+    pub env: HashMap<OsString, OsString>,
+    // Programs prepended to java, e.g. gamemoderun or prime-run
+    pub wrappers: Vec<OsString>,
 }
 
 impl<'a> GameCommand<'a> {
     fn build_classpath(
         version: &VersionInfo,
         hierarchy: &Hierarchy,
-    ) -> Result<OsString, JoinPathsError> {
-        env::join_paths(
-            version
-                .libraries
-                .iter()
-                .filter_map(|lib| {
-                    if lib.is_supported_by_rules() {
-                        lib.resources.artifact.as_ref()
-                    } else {
-                        None
-                    }
-                })
-                .map(|artifact| hierarchy.libraries_dir.join(&artifact.path))
-                .chain(iter::once(hierarchy.version_dir.join("client.jar"))),
-        )
+        features: &HashMap<&str, bool>,
+    ) -> Vec<PathBuf> {
+        version
+            .libraries
+            .iter()
+            .filter_map(|lib| {
+                if lib.is_supported_with(features) {
+                    lib.artifact_path()
+                } else {
+                    None
+                }
+            })
+            .map(|path| hierarchy.libraries_dir.join(path.as_ref()))
+            .chain(iter::once(hierarchy.client_jar_path()))
+            .collect()
     }
 
-    #[instrument(level = "trace")]
+    #[instrument(level = "trace", skip(options))]
     pub fn from_version_info(
         hierarchy: &'a Hierarchy,
         version: &'a VersionInfo,
-        features: &HashMap<&str, bool>,
-        username: &str,
+        feature_set: &FeatureSet,
+        options: &LaunchOptions<'_>,
     ) -> Self {
         const LAUNCHER_NAME: &str = env!("CARGO_PKG_NAME");
         const LAUNCHER_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+        let session = &options.session;
+        let demo = feature_set.demo;
+        // Resolution arguments are gated by the feature in modern versions
+        let features = feature_set
+            .clone()
+            .custom_resolution(feature_set.custom_resolution || options.resolution.is_some())
+            .to_map();
         let mut params = HashMap::new();
         params.insert("launcher_name", Cow::Borrowed(LAUNCHER_NAME.as_ref()));
+        params.insert("launcher_brand", Cow::Borrowed(LAUNCHER_NAME.as_ref()));
         params.insert("launcher_version", Cow::Borrowed(LAUNCHER_VERSION.as_ref()));
 
         params.insert(
@@ -84,7 +212,8 @@ impl<'a> GameCommand<'a> {
             Cow::Borrowed(hierarchy.assets_dir.as_os_str()),
         );
 
-        match Self::build_classpath(version, hierarchy) {
+        let classpath = Self::build_classpath(version, hierarchy, &features);
+        match env::join_paths(&classpath) {
             Ok(classpath) => {
                 trace!(?classpath, "Built classpath");
                 params.insert("classpath", Cow::Owned(classpath));
@@ -95,40 +224,767 @@ impl<'a> GameCommand<'a> {
         }
 
         params.insert("version_name", Cow::Borrowed(version.id.as_ref()));
-        params.insert("assets_index_name", Cow::Borrowed(version.assets.as_ref()));
-        params.insert("auth_player_name", Cow::Borrowed(username.as_ref()));
-        // TODO : and so on
+        params.insert(
+            "version_type",
+            Cow::Borrowed(version.release_type.as_str().as_ref()),
+        );
+        if let Some(assets) = &version.assets {
+            params.insert("assets_index_name", Cow::Borrowed(assets.as_ref()));
+        }
+        // Legacy versions are given the named layout instead of the assets root
+        let game_assets = match version.assets.as_deref() {
+            Some("legacy" | "pre-1.6") => {
+                Cow::Owned(hierarchy.legacy_assets_dir().into_os_string())
+            }
+            _ => Cow::Borrowed(hierarchy.assets_dir.as_os_str()),
+        };
+        params.insert("game_assets", game_assets);
+
+        params.insert("auth_player_name", Cow::Borrowed(session.username.as_ref()));
+        params.insert("auth_uuid", Cow::Borrowed(session.uuid.as_ref()));
+        params.insert(
+            "auth_access_token",
+            Cow::Borrowed(session.access_token.as_ref()),
+        );
+        params.insert(
+            "auth_session",
+            Cow::Owned(OsString::from(format!(
+                "token:{}:{}",
+                session.access_token, session.uuid
+            ))),
+        );
+        params.insert("auth_xuid", Cow::Borrowed(session.xuid.as_ref()));
+        params.insert("user_type", Cow::Borrowed(session.user_type.as_ref()));
+        params.insert("clientid", Cow::Borrowed(session.client_id.as_ref()));
+
+        if let Some((width, height)) = options.resolution {
+            params.insert(
+                "resolution_width",
+                Cow::Owned(OsString::from(width.to_string())),
+            );
+            params.insert(
+                "resolution_height",
+                Cow::Owned(OsString::from(height.to_string())),
+            );
+        }
+
+        for (key, value) in feature_set.params() {
+            params.insert(key, Cow::Borrowed(value.as_ref()));
+        }
 
         trace!(?params, "Gather params for substitution");
 
-        let jvm_args = version
+        let mut jvm_args: Vec<_> = version
             .arguments
             .iter_jvm_args(&features)
             .map(|arg| substitute_arg(arg, &params))
             .collect();
-        let game_args = version
+        // Added by the launcher itself
+        if os_name() == "osx" {
+            jvm_args.push(OsString::from("-Xdock:name=Minecraft"));
+            if let Some(icon) = dock_icon(hierarchy, version) {
+                let mut arg = OsString::from("-Xdock:icon=");
+                arg.push(icon);
+                jvm_args.push(arg);
+            }
+        }
+        if let Some(logging) = version.logging.as_ref().filter(|_| options.logging) {
+            let config_path = hierarchy
+                .assets_dir
+                .join("log_configs")
+                .join(&logging.client.config.id);
+            let params = HashMap::from([("path", Cow::Borrowed(config_path.as_os_str()))]);
+            jvm_args.push(substitute_arg(&logging.client.argument, &params));
+        }
+        let mut game_args: Vec<_> = version
             .arguments
             .iter_game_args(&features)
             .map(|arg| substitute_arg(arg, &params))
             .collect();
+        // Legacy arguments have no rules, so demo mode and resolution are requested explicitly
+        if matches!(version.arguments, Arguments::Legacy(_)) {
+            if demo {
+                game_args.push(OsString::from("--demo"));
+            }
+            if let Some((width, height)) = options.resolution {
+                game_args.extend(
+                    [
+                        "--width",
+                        &width.to_string(),
+                        "--height",
+                        &height.to_string(),
+                    ]
+                    .map(OsString::from),
+                );
+            }
+        }
+        if options.fullscreen {
+            game_args.push(OsString::from("--fullscreen"));
+        }
         trace!(?jvm_args, "Compiled jvm_args");
         trace!(?game_args, "Compiled game_args");
 
         Self {
             cwd: hierarchy.gamedir.as_path(),
-            main_class: &version.main_class,
+            entrypoint: Entrypoint::MainClass(&version.main_class),
+            classpath,
             jvm_args,
             game_args,
+            extra_jvm_args: Default::default(),
+            env: Default::default(),
+            wrappers: Default::default(),
+        }
+    }
+
+    // Server jar bundles its libraries, so neither classpath nor assets are needed
+    #[instrument(level = "trace")]
+    pub fn for_server(hierarchy: &'a Hierarchy, version: &'a VersionInfo) -> Self {
+        Self {
+            cwd: hierarchy.gamedir.as_path(),
+            jvm_args: Default::default(),
+            game_args: vec![OsString::from("nogui")],
+            entrypoint: Entrypoint::Jar(hierarchy.server_jar_path(&version.id)),
+            classpath: Default::default(),
+            extra_jvm_args: Default::default(),
+            env: Default::default(),
+            wrappers: Default::default(),
+        }
+    }
+
+    pub fn with_extra_jvm_args<I>(mut self, args: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<OsString>,
+    {
+        self.extra_jvm_args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn with_env(mut self, key: impl Into<OsString>, value: impl Into<OsString>) -> Self {
+        self.env.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn with_wrapper(mut self, program: impl Into<OsString>) -> Self {
+        self.wrappers.push(program.into());
+        self
+    }
+
+    // Files required to start, which are missing, so a repair may be offered instead of letting
+    // the game fail with ClassNotFoundException
+    pub fn validate(&self, hierarchy: &Hierarchy) -> Vec<PathBuf> {
+        let required: Vec<&Path> = match &self.entrypoint {
+            Entrypoint::MainClass(_) => self
+                .classpath
+                .iter()
+                .map(PathBuf::as_path)
+                .chain(iter::once(hierarchy.natives_dir.as_path()))
+                .collect(),
+            Entrypoint::Jar(jar) => vec![jar.as_path()],
+        };
+        required
+            .into_iter()
+            .filter(|path| !path.exists())
+            .map(Path::to_path_buf)
+            .collect()
+    }
+
+    // Wrappers first, then java and its arguments
+    pub fn to_argv(&self, java_path: impl AsRef<OsStr>) -> Vec<OsString> {
+        let mut argv = self.wrappers.clone();
+        argv.push(java_path.as_ref().to_owned());
+        argv.extend(self.jvm_args.iter().cloned());
+        argv.extend(self.extra_jvm_args.iter().cloned());
+        match &self.entrypoint {
+            Entrypoint::MainClass(main_class) => argv.push(OsString::from(main_class)),
+            Entrypoint::Jar(jar) => {
+                argv.push(OsString::from("-jar"));
+                argv.push(jar.clone().into_os_string());
+            }
+        }
+        argv.extend(self.game_args.iter().cloned());
+        argv
+    }
+
+    pub fn to_spec(&self, java_path: impl AsRef<OsStr>) -> LaunchSpec {
+        LaunchSpec {
+            argv: self.to_argv(java_path),
+            cwd: self.cwd.to_owned(),
+            env: self.env.clone(),
         }
     }
 
     #[instrument]
     pub fn build(&self, java_path: impl AsRef<OsStr> + Debug) -> Command {
-        let mut command = Command::new(java_path);
-        command.current_dir(self.cwd);
-        command.args(&self.jvm_args);
-        command.arg(OsStr::new(&self.main_class));
-        command.args(&self.game_args);
-        command
+        self.to_spec(java_path)
+            .command()
+            .expect("argv starts with the program")
+    }
+}
+
+// Everything needed to run the game, so it may be logged or wrapped by a sandbox
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LaunchSpec {
+    // Program is the first item
+    pub argv: Vec<OsString>,
+    pub cwd: PathBuf,
+    pub env: HashMap<OsString, OsString>,
+}
+
+impl LaunchSpec {
+    // None when argv was emptied
+    pub fn program(&self) -> Option<&OsStr> {
+        self.argv.first().map(OsString::as_os_str)
+    }
+
+    pub fn args(&self) -> &[OsString] {
+        self.argv.get(1..).unwrap_or_default()
+    }
+
+    pub fn command(&self) -> Option<Command> {
+        let mut command = Command::new(self.program()?);
+        command.args(self.args());
+        command.current_dir(&self.cwd);
+        command.envs(&self.env);
+        Some(command)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogSource {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Crash {
+    Report(Option<PathBuf>),
+    UncaughtException,
+    JvmStartup,
+}
+
+#[derive(Debug, Clone)]
+pub struct LogLine {
+    pub source: LogSource,
+    pub text: String,
+}
+
+impl LogLine {
+    pub fn crash(&self) -> Option<Crash> {
+        const REPORT_SAVED: &str = "Crash report saved to:";
+
+        let text = self.text.trim();
+        if let Some(i) = text.find(REPORT_SAVED) {
+            let path = text[i + REPORT_SAVED.len()..]
+                .trim_start_matches(|c: char| c.is_whitespace() || c == '#' || c == '@' || c == '!')
+                .trim();
+            Some(Crash::Report(
+                (!path.is_empty()).then(|| PathBuf::from(path)),
+            ))
+        } else if text.contains("---- Minecraft Crash Report ----") {
+            Some(Crash::Report(None))
+        } else if text.starts_with("Exception in thread") {
+            Some(Crash::UncaughtException)
+        } else if text.starts_with("Error: Could not find or load main class")
+            || text.starts_with("Error: Could not create the Java Virtual Machine")
+        {
+            Some(Crash::JvmStartup)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GameProcess {
+    child: Child,
+    lines: UnboundedReceiver<LogLine>,
+}
+
+impl GameProcess {
+    pub fn id(&self) -> Option<u32> {
+        self.child.id()
+    }
+
+    // Stdout and stderr lines are interleaved in order of arrival
+    pub async fn next_line(&mut self) -> Option<LogLine> {
+        self.lines.recv().await
+    }
+
+    pub async fn wait(&mut self) -> io::Result<ExitStatus> {
+        self.child.wait().await
+    }
+
+    pub async fn kill(&mut self) -> io::Result<()> {
+        self.child.kill().await
+    }
+}
+
+fn forward_lines<R>(reader: R, source: LogSource, sender: UnboundedSender<LogLine>)
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(reader);
+        let mut line = Vec::new();
+        let mut forwarding = true;
+        // Raw, as consoles of non-UTF-8 locales are common. Kept draining when nobody listens, so
+        // the game never blocks on a full pipe
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line).await {
+                Ok(0) => break,
+                Ok(_) if forwarding => {
+                    let text = line.strip_suffix(b"\n").unwrap_or(&line);
+                    let text = text.strip_suffix(b"\r").unwrap_or(text);
+                    let text = String::from_utf8_lossy(text).into_owned();
+                    forwarding = sender.send(LogLine { source, text }).is_ok();
+                }
+                Ok(_) => (),
+                Err(e) => {
+                    warn!(%e, ?source, "couldn't read game output");
+                    break;
+                }
+            }
+        }
+    });
+}
+
+#[instrument]
+pub fn spawn(command: Command) -> io::Result<GameProcess> {
+    let mut command = tokio::process::Command::from(command);
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    let mut child = command.spawn()?;
+
+    let (sender, lines) = mpsc::unbounded_channel();
+    if let Some(stdout) = child.stdout.take() {
+        forward_lines(stdout, LogSource::Stdout, sender.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward_lines(stderr, LogSource::Stderr, sender);
+    }
+    trace!(id = child.id(), "spawned game process");
+
+    Ok(GameProcess { child, lines })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        files::{sources::Server, ContentType, Dirs, SourcesList},
+        launch::features::QuickPlay,
+        testing::temp_dir,
+    };
+
+    use super::*;
+
+    const V1_20_1: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/data/1.20.1.json"
+    ));
+    // 1.13 snapshot with game arguments only
+    const V17W43A: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/data/17w43a.json"
+    ));
+    const V1_6_4: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/data/1.6.4.json"
+    ));
+
+    fn version(json: &str) -> VersionInfo {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn params<'a>(pairs: &'a [(&'a str, &'a str)]) -> HashMap<&'a str, Cow<'a, OsStr>> {
+        pairs
+            .iter()
+            .map(|(key, value)| (*key, Cow::Borrowed(OsStr::new(value))))
+            .collect()
+    }
+
+    fn substitute(arg: &str, pairs: &[(&str, &str)]) -> OsString {
+        substitute_arg(arg, &params(pairs))
+    }
+
+    #[test]
+    fn substitutes_known_placeholders() {
+        assert_eq!(substitute("${a}", &[("a", "x")]), "x");
+        assert_eq!(
+            substitute("-D${a}=${b}", &[("a", "x"), ("b", "y")]),
+            "-Dx=y"
+        );
+        assert_eq!(substitute("${a}${a}", &[("a", "${a}")]), "${a}${a}");
+    }
+
+    #[test]
+    fn escaped_placeholders_are_literal() {
+        assert_eq!(substitute("$${a}", &[("a", "x")]), "${a}");
+        assert_eq!(substitute("x$${a}y${a}", &[("a", "x")]), "x${a}yx");
+    }
+
+    #[test]
+    fn unclosed_placeholders_are_literal() {
+        assert_eq!(substitute("${a", &[("a", "x")]), "${a");
+        assert_eq!(substitute("${a $${b", &[("b", "x")]), "${a ${b");
+        assert_eq!(substitute("${a ${b", &[]), "${a ${b");
+    }
+
+    #[test]
+    fn unknown_placeholders_are_kept() {
+        assert_eq!(substitute("${unknown}", &[("a", "x")]), "${unknown}");
+        assert_eq!(substitute("${}", &[]), "${}");
+        // First '}' closes it, so nothing inside is substituted or unescaped
+        assert_eq!(substitute("${b${a}", &[("a", "x")]), "${b${a}");
+        assert_eq!(substitute("${b $${a}", &[("a", "x")]), "${b $${a}");
+    }
+
+    #[test]
+    fn multi_byte_input() {
+        assert_eq!(substitute("é${a}ö", &[("a", "ü")]), "éüö");
+        assert_eq!(substitute("€${ключ}€", &[("ключ", "€")]), "€€€");
+        assert_eq!(substitute("€${", &[]), "€${");
+        assert_eq!(substitute("$€{a}", &[("a", "x")]), "$€{a}");
+    }
+
+    // Randomized over the characters that matter, seeded so failures are reproducible
+    #[test]
+    fn never_panics_nor_consumes_unknown_placeholders() {
+        const ALPHABET: &[char] = &['$', '{', '}', 'a', 'é', '€', ' '];
+
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let known = params(&[("a", "x")]);
+        let none = params(&[]);
+        for _ in 0..20_000 {
+            let len = next() % 16;
+            let arg: String = (0..len)
+                .map(|_| ALPHABET[(next() % ALPHABET.len() as u64) as usize])
+                .collect();
+            substitute_arg(&arg, &known);
+            // Without escapes and known keys every char is kept as is
+            if !arg.contains("$$") {
+                assert_eq!(substitute_arg(&arg, &none), OsString::from(&arg), "{arg:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn argv_order() {
+        let version = version(V1_20_1);
+        let dirs = Dirs::new(PathBuf::from("/mc"));
+        let hierarchy = Hierarchy::new(&dirs, &version.id);
+        let options = LaunchOptions::new(Session::offline("Steve"));
+        let command =
+            GameCommand::from_version_info(&hierarchy, &version, &FeatureSet::default(), &options)
+                .with_wrapper("gamemoderun")
+                .with_extra_jvm_args(["-Xmx4G"])
+                .with_env("MESA_GL_VERSION_OVERRIDE", "4.5");
+        let spec = command.to_spec("java");
+        let argv: Vec<_> = spec.argv.iter().map(|arg| arg.to_str().unwrap()).collect();
+
+        assert_eq!(argv[..2], ["gamemoderun", "java"]);
+        let position = |arg: &str| argv.iter().position(|a| *a == arg).unwrap();
+        // Version's jvm arguments, then extra ones, then the main class and game arguments
+        assert!(position("-cp") < position("-Xmx4G"));
+        assert!(position("-Xmx4G") < position("net.minecraft.client.main.Main"));
+        assert_eq!(
+            position("net.minecraft.client.main.Main") + 1,
+            position("--username")
+        );
+        assert_eq!(argv.last(), Some(&"release"));
+        assert_eq!(spec.program(), Some(OsStr::new("gamemoderun")));
+        assert_eq!(
+            spec.env.get(OsStr::new("MESA_GL_VERSION_OVERRIDE")),
+            Some(&OsString::from("4.5"))
+        );
+        assert_eq!(spec.cwd, Path::new("/mc"));
+    }
+
+    fn argv(
+        version: &VersionInfo,
+        feature_set: &FeatureSet,
+        options: &LaunchOptions<'_>,
+    ) -> Vec<String> {
+        let dirs = Dirs::new(PathBuf::from("/mc"));
+        let hierarchy = Hierarchy::new(&dirs, &version.id);
+        GameCommand::from_version_info(&hierarchy, version, feature_set, options)
+            .to_argv("java")
+            .into_iter()
+            .map(|arg| arg.into_string().unwrap())
+            .collect()
+    }
+
+    fn contains(argv: &[String], args: &[&str]) -> bool {
+        argv.windows(args.len()).any(|window| window == args)
+    }
+
+    #[test]
+    fn default_features_enable_no_arguments() {
+        let argv = argv(
+            &version(V1_20_1),
+            &FeatureSet::default(),
+            &LaunchOptions::new(Session::offline("Steve")),
+        );
+        for arg in [
+            "--demo",
+            "--width",
+            "--quickPlayPath",
+            "--quickPlaySingleplayer",
+        ] {
+            assert!(!argv.iter().any(|a| a == arg), "{arg}");
+        }
+    }
+
+    #[test]
+    fn features_enable_their_arguments() {
+        let features = FeatureSet::default()
+            .demo(true)
+            .quick_play(QuickPlay::Singleplayer("world".to_owned()))
+            .quick_play_path("quickplay.json");
+        let argv = argv(
+            &version(V1_20_1),
+            &features,
+            &LaunchOptions::new(Session::offline("Steve")),
+        );
+        assert!(contains(&argv, &["--demo"]));
+        assert!(contains(&argv, &["--quickPlaySingleplayer", "world"]));
+        assert!(contains(&argv, &["--quickPlayPath", "quickplay.json"]));
+        assert!(!argv.iter().any(|a| a == "--quickPlayMultiplayer"));
+    }
+
+    #[test]
+    fn missing_jvm_arguments_default() {
+        let version = version(V17W43A);
+        let Arguments::Modern { game, jvm } = &version.arguments else {
+            panic!("arguments aren't modern");
+        };
+        assert!(jvm.is_empty());
+        assert!(!game.is_empty());
+
+        let argv = argv(
+            &version,
+            &FeatureSet::default(),
+            &LaunchOptions::new(Session::offline("Steve")),
+        );
+        assert!(contains(
+            &argv,
+            &[
+                "-Djava.library.path=/mc/natives/17w43a",
+                "-Dminecraft.launcher.brand=mcl-api",
+                concat!("-Dminecraft.launcher.version=", env!("CARGO_PKG_VERSION")),
+                "-cp",
+                "/mc/versions/17w43a/client.jar",
+            ]
+        ));
+        assert_eq!(
+            argv.iter().any(|arg| arg == "-XstartOnFirstThread"),
+            os_name() == "osx"
+        );
+        assert!(contains(
+            &argv,
+            &["net.minecraft.client.main.Main", "--username", "Steve"]
+        ));
+    }
+
+    #[test]
+    fn legacy_version_argv() {
+        let session = Session::offline("Steve");
+        let argv = argv(
+            &version(V1_6_4),
+            &FeatureSet::default(),
+            &LaunchOptions::new(session),
+        );
+        assert!(contains(&argv, &["-Djava.library.path=/mc/natives/1.6.4"]));
+        let classpath = &argv[argv.iter().position(|arg| arg == "-cp").unwrap() + 1];
+        assert!(classpath.contains("jopt-simple-4.5.jar"));
+        assert!(classpath.ends_with("/mc/versions/1.6.4/client.jar"));
+        assert!(contains(
+            &argv,
+            &["net.minecraft.client.main.Main", "--username", "Steve"]
+        ));
+        assert!(contains(
+            &argv,
+            &["--session", &format!("token:0:{}", session.uuid)]
+        ));
+        assert!(contains(&argv, &["--version", "1.6.4", "--gameDir", "/mc"]));
+        assert!(contains(
+            &argv,
+            &["--assetsDir", "/mc/assets/virtual/legacy"]
+        ));
+        assert!(!argv.iter().any(|arg| arg.contains("${")));
+    }
+
+    const ICON_HASH: &str = "a1b2c3d4e5f60718293a4b5c6d7e8f9012345678";
+
+    fn write_index(dirs: &Dirs, id: &str, index: &str) {
+        let indexes = dirs.assets.join("indexes");
+        fs::create_dir_all(&indexes).unwrap();
+        fs::write(indexes.join(format!("{id}.json")), index).unwrap();
+    }
+
+    #[test]
+    fn dock_icon_of_hashed_assets() {
+        let dirs = Dirs::new(temp_dir("dock-icon-hashed"));
+        let version = version(V1_20_1);
+        let hierarchy = Hierarchy::new(&dirs, &version.id);
+        write_index(
+            &dirs,
+            version.assets.as_deref().unwrap(),
+            &format!(
+                r#"{{"objects": {{"icons/minecraft.icns": {{"hash": "{ICON_HASH}", "size": 4}}}}}}"#
+            ),
+        );
+        // Not synced yet
+        assert_eq!(dock_icon(&hierarchy, &version), None);
+
+        let icon = dirs.assets.join("objects/a1").join(ICON_HASH);
+        fs::create_dir_all(icon.parent().unwrap()).unwrap();
+        fs::write(&icon, "icns").unwrap();
+        assert_eq!(dock_icon(&hierarchy, &version), Some(icon));
+    }
+
+    #[test]
+    fn dock_icon_of_legacy_assets() {
+        let dirs = Dirs::new(temp_dir("dock-icon-legacy"));
+        let version = version(V1_6_4);
+        let hierarchy = Hierarchy::new(&dirs, &version.id);
+        write_index(
+            &dirs,
+            "legacy",
+            &format!(
+                r#"{{"virtual": true, "objects": {{"icons/minecraft.icns": {{"hash": "{ICON_HASH}", "size": 4}}}}}}"#
+            ),
+        );
+        let icon = hierarchy.legacy_assets_dir().join("icons/minecraft.icns");
+        fs::create_dir_all(icon.parent().unwrap()).unwrap();
+        fs::write(&icon, "icns").unwrap();
+        assert_eq!(dock_icon(&hierarchy, &version), Some(icon));
+    }
+
+    // Host can't be faked, so these are checked to follow it
+    #[test]
+    fn macos_arguments_follow_the_host() {
+        let argv = argv(
+            &version(V1_20_1),
+            &FeatureSet::default(),
+            &LaunchOptions::new(Session::offline("Steve")),
+        );
+        let on_osx = os_name() == "osx";
+        assert_eq!(argv.iter().any(|arg| arg == "-XstartOnFirstThread"), on_osx);
+        assert_eq!(
+            argv.iter().any(|arg| arg == "-Xdock:name=Minecraft"),
+            on_osx
+        );
+    }
+
+    #[test]
+    fn resolution_arguments_only_when_set() {
+        let version = version(V1_20_1);
+        let session = Session::offline("Steve");
+        let argv_of = |options| argv(&version, &FeatureSet::default(), &options);
+
+        let default = argv_of(LaunchOptions::new(session));
+        assert!(!default.iter().any(|arg| arg == "--width"));
+        assert!(!default.iter().any(|arg| arg == "--fullscreen"));
+
+        let sized = argv_of(LaunchOptions::new(session).with_resolution(1280, 720));
+        assert!(contains(&sized, &["--width", "1280", "--height", "720"]));
+
+        let fullscreen = argv_of(LaunchOptions::new(session).with_fullscreen(true));
+        assert!(contains(&fullscreen, &["--fullscreen"]));
+        assert!(!fullscreen.iter().any(|arg| arg == "--width"));
+    }
+
+    #[test]
+    fn legacy_resolution_arguments() {
+        let argv = argv(
+            &version(V1_6_4),
+            &FeatureSet::default(),
+            &LaunchOptions::new(Session::offline("Steve")).with_resolution(854, 480),
+        );
+        assert!(contains(&argv, &["--width", "854", "--height", "480"]));
+    }
+
+    #[test]
+    fn synced_files_are_on_the_classpath() {
+        let version = version(V1_20_1);
+        let dirs = Dirs {
+            versions: PathBuf::from("/shared/versions"),
+            ..Dirs::new(PathBuf::from("/mc"))
+        };
+        let hierarchy = Hierarchy::new(&dirs, &version.id);
+        let options = LaunchOptions::new(Session::offline("Steve"));
+        let argv =
+            GameCommand::from_version_info(&hierarchy, &version, &FeatureSet::default(), &options)
+                .to_argv("java");
+        let classpath = &argv[argv.iter().position(|arg| arg == "-cp").unwrap() + 1];
+        let classpath: Vec<_> = env::split_paths(classpath).collect();
+
+        let client_jar = (&version)
+            .sources()
+            .find(|source| source.r#type == ContentType::ClientJar)
+            .unwrap();
+        assert_eq!(client_jar.local_path(&dirs), hierarchy.client_jar_path());
+        assert_eq!(
+            classpath.last(),
+            Some(&PathBuf::from("/shared/versions/1.20.1/client.jar"))
+        );
+        for library in (&version)
+            .sources()
+            .filter(|source| source.r#type == ContentType::Library)
+        {
+            assert!(
+                classpath.contains(&library.local_path(&dirs)),
+                "{}",
+                library.name
+            );
+        }
+    }
+
+    #[test]
+    fn emptied_spec_has_no_command() {
+        let spec = LaunchSpec {
+            argv: Vec::new(),
+            cwd: PathBuf::from("/mc"),
+            env: HashMap::new(),
+        };
+        assert_eq!(spec.program(), None);
+        assert!(spec.args().is_empty());
+        assert!(spec.command().is_none());
+
+        let spec = LaunchSpec {
+            argv: vec![OsString::from("java")],
+            ..spec
+        };
+        assert_eq!(spec.program(), Some(OsStr::new("java")));
+        assert!(spec.args().is_empty());
+        assert!(spec.command().is_some());
+    }
+
+    #[test]
+    fn server_runs_the_synced_jar() {
+        let version = version(V1_20_1);
+        let dirs = Dirs::new(PathBuf::from("/mc"));
+        let hierarchy = Hierarchy::new(&dirs, &version.id);
+        let server_jar = Server(&version).sources().next().unwrap();
+        let argv = GameCommand::for_server(&hierarchy, &version)
+            .with_extra_jvm_args(["-Xmx2G"])
+            .to_argv("java");
+        assert_eq!(
+            argv,
+            [
+                OsString::from("java"),
+                OsString::from("-Xmx2G"),
+                OsString::from("-jar"),
+                server_jar.local_path(&dirs).into_os_string(),
+                OsString::from("nogui"),
+            ]
+        );
     }
 }
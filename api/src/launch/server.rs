@@ -0,0 +1,76 @@
+use std::{
+    ffi::OsStr,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use tracing::{instrument, trace};
+
+use crate::{files::Hierarchy, metadata::game::VersionInfo};
+
+use super::process::GameCommand;
+
+const SCRIPT_NAME: &str = if cfg!(windows) {
+    "start.bat"
+} else {
+    "start.sh"
+};
+
+#[cfg(windows)]
+fn script(argv: &[std::ffi::OsString]) -> String {
+    let command: Vec<_> = argv
+        .iter()
+        .map(|arg| format!("\"{}\"", arg.to_string_lossy()))
+        .collect();
+    format!(
+        "@echo off\r\ncd /d \"%~dp0\"\r\n{} %*\r\n",
+        command.join(" ")
+    )
+}
+
+#[cfg(not(windows))]
+fn script(argv: &[std::ffi::OsString]) -> String {
+    let command: Vec<_> = argv
+        .iter()
+        .map(|arg| format!("'{}'", arg.to_string_lossy().replace('\'', r"'\''")))
+        .collect();
+    format!(
+        "#!/bin/sh\ncd \"$(dirname \"$0\")\"\nexec {} \"$@\"\n",
+        command.join(" ")
+    )
+}
+
+// Kept when present, so an accepted EULA isn't reverted
+fn write_new(path: &Path, content: &str) -> io::Result<()> {
+    match OpenOptions::new().write(true).create_new(true).open(path) {
+        Ok(mut file) => file.write_all(content.as_bytes()),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+// Sets up the game dir to be run by the start script, the EULA must be accepted by the user (by
+// setting eula=true). Returns the path of the script
+#[instrument(skip(version))]
+pub fn prepare(
+    hierarchy: &Hierarchy,
+    version: &VersionInfo,
+    java_path: &OsStr,
+) -> io::Result<PathBuf> {
+    let dir = &hierarchy.gamedir;
+    fs::create_dir_all(dir)?;
+    write_new(&dir.join("eula.txt"), "eula=false\n")?;
+
+    let argv = GameCommand::for_server(hierarchy, version).to_argv(java_path);
+    let script_path = dir.join(SCRIPT_NAME);
+    fs::write(&script_path, script(&argv))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&script_path, fs::Permissions::from_mode(0o755))?;
+    }
+    trace!(?script_path, "prepared server");
+
+    Ok(script_path)
+}
@@ -1,4 +1,11 @@
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod client;
 pub mod files;
+pub mod launch;
 pub mod metadata;
 pub mod resources;
 pub mod tasks;
+
+#[cfg(test)]
+mod testing;
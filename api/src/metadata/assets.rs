@@ -1,4 +1,7 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{
+    collections::{BTreeMap, HashSet},
+    str::FromStr,
+};
 
 use serde_derive::Deserialize;
 
@@ -21,10 +24,42 @@ pub struct AssetMetadata {
     pub size: u64,
 }
 
+// <first 2 chars>/<hash>, as laid out on the CDN and locally; None for malformed hashes
+pub fn hash_path(hash: &str) -> Option<String> {
+    let prefix = hash.get(..2)?;
+    Some(format!("{prefix}/{hash}"))
+}
+
+impl AssetMetadata {
+    pub fn object_path(&self) -> Option<String> {
+        hash_path(&self.hash)
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct AssetIndex {
     pub map_to_resources: Option<bool>,
+    #[serde(rename = "virtual")]
+    pub is_virtual: Option<bool>,
     #[serde(default = "default_base_url")]
     pub origin: Url,
-    pub objects: HashMap<String, AssetMetadata>,
+    // Sorted by name, so sources are yielded in the same order every time
+    pub objects: BTreeMap<String, AssetMetadata>,
+}
+
+impl AssetIndex {
+    // Assets are looked up by their names, not hashes, i.e. by versions prior to 1.7.3
+    pub fn is_legacy(&self) -> bool {
+        self.map_to_resources.unwrap_or(false) || self.is_virtual.unwrap_or(false)
+    }
+
+    // Objects sharing a hash are downloaded once
+    pub fn total_size(&self) -> u64 {
+        let mut seen = HashSet::new();
+        self.objects
+            .values()
+            .filter(|object| seen.insert(&object.hash))
+            .map(|object| object.size)
+            .sum()
+    }
 }
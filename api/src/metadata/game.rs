@@ -1,11 +1,12 @@
-use std::{collections::HashMap, env::consts, iter};
+use std::{borrow::Cow, collections::HashMap, env::consts, iter, mem, sync::OnceLock};
 
 use chrono::{DateTime, Utc};
 use serde_derive::Deserialize;
 use serde_with::{formats::SpaceSeparator, serde_as, OneOrMany, StringWithSeparator};
+use tracing::{trace, warn};
 use url::Url;
 
-use super::manifest::ReleaseType;
+use super::{manifest::ReleaseType, maven::MavenCoord};
 
 #[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -14,25 +15,25 @@ pub enum RuleAction {
     Disallow,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct OsDescription {
     pub name: Option<String>,
     pub version: Option<String>,
     pub arch: Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Rule {
     pub action: RuleAction,
     pub os: Option<OsDescription>,
     pub features: Option<HashMap<String, bool>>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Rules(Vec<Rule>);
 
 #[serde_as]
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum Argument {
     Plain(String),
@@ -44,25 +45,27 @@ pub enum Argument {
 }
 
 #[serde_as]
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub enum Arguments {
     #[serde(rename = "arguments")]
     Modern {
         game: Vec<Argument>,
+        // Missing in some versions in between, e.g. 1.13 snapshots
+        #[serde(default)]
         jvm: Vec<Argument>,
     },
     #[serde(rename = "minecraftArguments")]
     Legacy(#[serde_as(as = "StringWithSeparator::<SpaceSeparator, String>")] Vec<String>),
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Resource {
     pub sha1: String,
     pub size: u64,
     pub url: Url,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AssetIndexResource {
     #[serde(flatten)]
@@ -71,14 +74,14 @@ pub struct AssetIndexResource {
     pub total_size: u64,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct LoggerConfig {
     #[serde(flatten)]
     pub resource: Resource,
     pub id: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct LoggerDescription {
     pub argument: String,
     #[serde(rename = "type")]
@@ -87,59 +90,71 @@ pub struct LoggerDescription {
     pub config: LoggerConfig,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Logging {
     pub client: LoggerDescription,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct LibraryResource {
     #[serde(flatten)]
     pub resource: Resource,
     pub path: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, Default)]
 pub struct LibraryResources {
     pub artifact: Option<LibraryResource>,
     #[serde(rename = "classifiers")]
     pub other: Option<HashMap<String, LibraryResource>>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
+pub struct LibraryExtract {
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct Library {
-    #[serde(rename = "downloads")]
+    #[serde(rename = "downloads", default)]
     pub resources: LibraryResources,
     pub name: String,
+    // Maven repository for libraries without downloads, e.g. in Fabric profiles
+    pub url: Option<Url>,
     pub rules: Option<Rules>,
+    pub extract: Option<LibraryExtract>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct JavaVersion {
     pub component: String,
     pub major_version: usize,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Downloads {
     pub client: Resource,
     pub server: Option<Resource>,
+    pub client_mappings: Option<Resource>,
+    pub server_mappings: Option<Resource>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct VersionInfo {
     pub id: String,
+    pub inherits_from: Option<String>,
     #[serde(rename = "type")]
     pub release_type: ReleaseType,
-    pub minimum_launcher_version: usize,
+    pub minimum_launcher_version: Option<usize>,
     pub release_time: DateTime<Utc>,
     pub time: DateTime<Utc>,
     pub libraries: Vec<Library>,
-    pub downloads: Downloads,
-    pub asset_index: AssetIndexResource,
-    pub assets: String,
+    pub downloads: Option<Downloads>,
+    pub asset_index: Option<AssetIndexResource>,
+    pub assets: Option<String>,
     pub main_class: String,
     #[serde(flatten)]
     pub arguments: Arguments,
@@ -149,6 +164,27 @@ pub struct VersionInfo {
     pub compliance_level: Option<usize>,
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ArgumentMergeStrategy {
+    // Parent's arguments first, e.g. Fabric
+    #[default]
+    Append,
+    // Child's arguments only, unless it has none
+    Replace,
+}
+
+impl ArgumentMergeStrategy {
+    fn merge(self, child: &mut Vec<Argument>, parent: &[Argument]) {
+        match self {
+            Self::Append => {
+                child.splice(0..0, parent.iter().cloned());
+            }
+            Self::Replace if child.is_empty() => child.extend_from_slice(parent),
+            Self::Replace => (),
+        }
+    }
+}
+
 impl RuleAction {
     pub fn value(self) -> bool {
         match self {
@@ -165,18 +201,37 @@ impl RuleAction {
     }
 }
 
+// Names used by Mojang in rules, which differ from Rust's for macOS
+pub fn os_name() -> &'static str {
+    match consts::OS {
+        "macos" => "osx",
+        os => os,
+    }
+}
+
+// Platform keys of Mojang's java runtimes, None where no runtime is provided
+pub fn runtime_platform() -> Option<&'static str> {
+    match (consts::OS, consts::ARCH) {
+        ("linux", "x86_64") => Some("linux"),
+        ("linux", "x86") => Some("linux-i386"),
+        ("macos", "x86_64") => Some("mac-os"),
+        ("macos", "aarch64") => Some("mac-os-arm64"),
+        ("windows", "x86_64") => Some("windows-x64"),
+        ("windows", "x86") => Some("windows-x86"),
+        ("windows", "aarch64") => Some("windows-arm64"),
+        _ => None,
+    }
+}
+
 impl Rule {
-    fn calculate_action(&self, params: &HashMap<&str, bool>) -> RuleAction {
+    // Whether the conditions hold, regardless of the action
+    pub fn matches(&self, params: &HashMap<&str, bool>) -> bool {
         if let Some(os) = &self.os {
-            if let Some(name) = &os.name {
-                if name != consts::OS {
-                    return self.action.invert();
-                }
+            if os.name.as_ref().is_some_and(|name| name != os_name()) {
+                return false;
             }
-            if let Some(arch) = &os.arch {
-                if arch != consts::ARCH {
-                    return self.action.invert();
-                }
+            if os.arch.as_ref().is_some_and(|arch| arch != consts::ARCH) {
+                return false;
             }
             if let Some(_version) = &os.version {
                 // TODO: version parsing using crate
@@ -185,21 +240,23 @@ impl Rule {
         if let Some(features) = &self.features {
             for (k, v) in features.iter() {
                 if params.get(k.as_str()).unwrap_or(&false) != v {
-                    return self.action.invert();
+                    return false;
                 }
             }
         }
-        self.action
-    }
-
-    pub fn is_allowed(&self, params: &HashMap<&str, bool>) -> bool {
-        self.calculate_action(params).value()
+        true
     }
 }
 
 impl Rules {
+    // Last matching rule wins, e.g. [allow, disallow osx] is everything but macOS, and nothing is
+    // allowed when no rule matches
     pub fn is_allowed(&self, params: &HashMap<&str, bool>) -> bool {
-        !self.0.iter().any(|rule| !rule.is_allowed(params))
+        self.0
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(params))
+            .is_some_and(|rule| rule.action.value())
     }
 }
 
@@ -221,17 +278,48 @@ impl Argument {
     }
 }
 
+// Used by the launcher for versions not specifying their own ones, i.e. legacy ones
+fn default_jvm_args() -> &'static [Argument] {
+    static ARGS: OnceLock<Vec<Argument>> = OnceLock::new();
+    ARGS.get_or_init(|| {
+        let plain = |arg: &str| Argument::Plain(arg.to_owned());
+        vec![
+            Argument::RuleSpecific {
+                value: vec!["-XstartOnFirstThread".to_owned()],
+                rules: Rules(vec![Rule {
+                    action: RuleAction::Allow,
+                    os: Some(OsDescription {
+                        name: Some("osx".to_owned()),
+                        version: None,
+                        arch: None,
+                    }),
+                    features: None,
+                }]),
+            },
+            plain("-Djava.library.path=${natives_directory}"),
+            plain("-Dminecraft.launcher.brand=${launcher_name}"),
+            plain("-Dminecraft.launcher.version=${launcher_version}"),
+            plain("-cp"),
+            plain("${classpath}"),
+        ]
+    })
+}
+
 impl Arguments {
     pub fn iter_jvm_args<'a, 'b: 'a>(
         &'a self,
         params: &'b HashMap<&str, bool>,
     ) -> Box<dyn Iterator<Item = &'a str> + 'a> {
         match self {
-            Self::Modern { jvm, .. } => Box::new(
+            Self::Modern { jvm, .. } if !jvm.is_empty() => Box::new(
                 jvm.iter()
                     .flat_map(|argument| argument.iter_strings(params)),
             ),
-            Self::Legacy(_) => Box::new(iter::empty()),
+            Self::Modern { .. } | Self::Legacy(_) => Box::new(
+                default_jvm_args()
+                    .iter()
+                    .flat_map(|argument| argument.iter_strings(params)),
+            ),
         }
     }
 
@@ -249,24 +337,472 @@ impl Arguments {
     }
 }
 
+// Classifier from the coordinate takes precedence over the passed one
+pub fn build_library_path(name: &str, classifier: Option<&str>) -> Option<String> {
+    let coord: MavenCoord = name.parse().ok()?;
+    Some(coord.or_classifier(classifier).to_path())
+}
+
 impl Library {
+    // Path is built from the name only for libraries from a maven repository
+    pub fn artifact_path(&self) -> Option<Cow<'_, str>> {
+        match &self.resources.artifact {
+            Some(artifact) => Some(Cow::Borrowed(&artifact.path)),
+            None if self.url.is_some() => build_library_path(&self.name, None).map(Cow::Owned),
+            None => None,
+        }
+    }
+
+    pub fn coord(&self) -> Option<MavenCoord> {
+        self.name.parse().ok()
+    }
+
     pub fn is_supported_by_rules(&self) -> bool {
+        self.is_supported_with(&HashMap::new())
+    }
+
+    // Rules may also depend on features, e.g. libraries only needed by demo users
+    pub fn is_supported_with(&self, features: &HashMap<&str, bool>) -> bool {
         self.rules
             .as_ref()
-            .map(|rules| rules.is_allowed(&HashMap::new()))
+            .map(|rules| rules.is_allowed(features))
             .unwrap_or(true)
     }
 }
 
+impl VersionInfo {
+    // Known before the asset index itself is fetched
+    pub fn assets_total_size(&self) -> Option<u64> {
+        self.asset_index
+            .as_ref()
+            .map(|asset_index| asset_index.total_size)
+    }
+
+    // Child's fields win, parent's libraries go first and only the highest version of each is kept.
+    // Arguments are appended like Minecraft's own inheritance does
+    pub fn merge_with(&mut self, parent: &VersionInfo) {
+        self.merge_with_strategy(parent, ArgumentMergeStrategy::Append)
+    }
+
+    // Complete legacy arguments of the child are kept, the parent's ones become plain game ones
+    pub fn merge_with_strategy(&mut self, parent: &VersionInfo, strategy: ArgumentMergeStrategy) {
+        // Only the highest version of an artifact is kept, the child's one on a tie
+        let parent_coords: Vec<_> = parent.libraries.iter().map(Library::coord).collect();
+        let mut parent_kept = vec![true; parent.libraries.len()];
+        let child_libraries: Vec<_> = mem::take(&mut self.libraries)
+            .into_iter()
+            .filter(|lib| {
+                let Some(coord) = lib.coord() else {
+                    return true;
+                };
+                let mut kept = true;
+                for (i, parent_coord) in parent_coords.iter().enumerate() {
+                    let Some(parent_coord) = parent_coord
+                        .as_ref()
+                        .filter(|parent_coord| coord.is_same_artifact(parent_coord))
+                    else {
+                        continue;
+                    };
+                    if coord.cmp_version(parent_coord).is_lt() {
+                        warn!(%coord, by = %parent_coord, "library shadowed by parent's one");
+                        kept = false;
+                    } else {
+                        trace!(coord = %parent_coord, by = %coord, "library overridden");
+                        parent_kept[i] = false;
+                    }
+                }
+                kept
+            })
+            .collect();
+        self.libraries = parent
+            .libraries
+            .iter()
+            .zip(parent_kept)
+            .filter(|(_, kept)| *kept)
+            .map(|(lib, _)| lib.clone())
+            .chain(child_libraries)
+            .collect();
+
+        match (&mut self.arguments, &parent.arguments) {
+            (
+                Arguments::Modern { game, jvm },
+                Arguments::Modern {
+                    game: parent_game,
+                    jvm: parent_jvm,
+                },
+            ) => {
+                strategy.merge(game, parent_game);
+                strategy.merge(jvm, parent_jvm);
+            }
+            // Parent's jvm arguments are the implicit default ones
+            (Arguments::Modern { game, jvm }, Arguments::Legacy(parent_game)) => {
+                strategy.merge(jvm, default_jvm_args());
+                let parent_game: Vec<_> =
+                    parent_game.iter().cloned().map(Argument::Plain).collect();
+                strategy.merge(game, &parent_game);
+            }
+            (Arguments::Legacy(_), _) => (),
+        }
+
+        self.minimum_launcher_version = self
+            .minimum_launcher_version
+            .or(parent.minimum_launcher_version);
+        self.downloads = self.downloads.take().or_else(|| parent.downloads.clone());
+        self.asset_index = self
+            .asset_index
+            .take()
+            .or_else(|| parent.asset_index.clone());
+        self.assets = self.assets.take().or_else(|| parent.assets.clone());
+        self.java_version = self
+            .java_version
+            .take()
+            .or_else(|| parent.java_version.clone());
+        self.logging = self.logging.take().or_else(|| parent.logging.clone());
+        self.compliance_level = self.compliance_level.or(parent.compliance_level);
+        // Nothing is left to inherit once the parent is resolved itself
+        self.inherits_from = parent.inherits_from.clone();
+    }
+}
+
 impl LibraryResources {
+    // Classifiers in order of preference, arch-specific ones go first
+    fn native_classifiers() -> &'static [&'static str] {
+        match (consts::OS, consts::ARCH) {
+            ("linux", "aarch64") => &["natives-linux-arm64"],
+            ("linux", "arm") => &["natives-linux-arm32"],
+            ("linux", _) => &["natives-linux"],
+            ("macos", "aarch64") => &["natives-macos-arm64", "natives-osx-arm64"],
+            ("macos", _) => &["natives-macos", "natives-osx"],
+            ("windows", "aarch64") => &["natives-windows-arm64"],
+            ("windows", "x86") => &[
+                "natives-windows-x86",
+                "natives-windows-32",
+                "natives-windows",
+            ],
+            ("windows", _) => &["natives-windows", "natives-windows-64"],
+            _ => &[],
+        }
+    }
+
     pub fn get_native_for_os(&self) -> Option<&LibraryResource> {
-        let native_str: &'static str = match consts::OS {
-            "macos" if consts::ARCH == "aarch64" => "natives-macos-arm64",
-            "linux" => "natives-linux",
-            "windows" => "natives-windows",
-            "macos" => "natives-macos",
-            _ => panic!("unsupported target"),
+        let other = self.other.as_ref()?;
+        Self::native_classifiers()
+            .iter()
+            .find_map(|classifier| other.get(*classifier))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Trimmed 1.20.1
+    const VANILLA: &str = r#"{
+        "id": "1.20.1",
+        "type": "release",
+        "time": "2023-06-12T13:25:51+00:00",
+        "releaseTime": "2023-06-07T09:35:21+00:00",
+        "mainClass": "net.minecraft.client.main.Main",
+        "assets": "5",
+        "assetIndex": {
+            "id": "5",
+            "sha1": "29b34837a3a3a6c0a2c0d0b9111532b1a294e89b",
+            "size": 411269,
+            "totalSize": 618451616,
+            "url": "https://piston-meta.mojang.com/v1/packages/29b34837a3a3a6c0a2c0d0b9111532b1a294e89b/5.json"
+        },
+        "downloads": {
+            "client": {
+                "sha1": "0c3ec587af28e5a785c0b4a7b8a30f9a8f78f838",
+                "size": 23028853,
+                "url": "https://piston-data.mojang.com/v1/objects/0c3ec587af28e5a785c0b4a7b8a30f9a8f78f838/client.jar"
+            }
+        },
+        "javaVersion": {"component": "java-runtime-gamma", "majorVersion": 17},
+        "complianceLevel": 1,
+        "arguments": {
+            "game": ["--username", "${auth_player_name}", "--version", "${version_name}"],
+            "jvm": ["-Djava.library.path=${natives_directory}", "-cp", "${classpath}"]
+        },
+        "libraries": [
+            {
+                "name": "com.google.guava:guava:31.1-jre",
+                "downloads": {
+                    "artifact": {
+                        "path": "com/google/guava/guava/31.1-jre/guava-31.1-jre.jar",
+                        "sha1": "60458f877d055d0c9114d9e1a2efb737b4bc282c",
+                        "size": 2959479,
+                        "url": "https://libraries.minecraft.net/com/google/guava/guava/31.1-jre/guava-31.1-jre.jar"
+                    }
+                }
+            },
+            {
+                "name": "com.mojang:brigadier:1.1.8",
+                "downloads": {
+                    "artifact": {
+                        "path": "com/mojang/brigadier/1.1.8/brigadier-1.1.8.jar",
+                        "sha1": "5244ce82c3337bba4a196a3ce858bfaecc74404a",
+                        "size": 77392,
+                        "url": "https://libraries.minecraft.net/com/mojang/brigadier/1.1.8/brigadier-1.1.8.jar"
+                    }
+                }
+            }
+        ]
+    }"#;
+
+    // Trimmed Fabric loader profile
+    const FABRIC: &str = r#"{
+        "id": "fabric-loader-0.14.21-1.20.1",
+        "inheritsFrom": "1.20.1",
+        "type": "release",
+        "time": "2023-06-12T13:25:51+00:00",
+        "releaseTime": "2023-06-12T13:25:51+00:00",
+        "mainClass": "net.fabricmc.loader.impl.launch.knot.KnotClient",
+        "arguments": {"game": [], "jvm": ["-DFabricMcEmu= net.minecraft.client.main.Main "]},
+        "libraries": [
+            {"name": "org.ow2.asm:asm:9.5", "url": "https://maven.fabricmc.net/"},
+            {"name": "net.fabricmc:fabric-loader:0.14.21", "url": "https://maven.fabricmc.net/"}
+        ]
+    }"#;
+
+    fn version(json: &str) -> VersionInfo {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn library_names(version: &VersionInfo) -> Vec<&str> {
+        version
+            .libraries
+            .iter()
+            .map(|lib| lib.name.as_str())
+            .collect()
+    }
+
+    fn plain(args: &[Argument]) -> Vec<&str> {
+        args.iter()
+            .flat_map(|arg| arg.iter_strings(&HashMap::new()))
+            .collect()
+    }
+
+    fn rules(json: &str) -> Rules {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn allow_then_disallow_osx() {
+        // As in the lwjgl libraries of 1.12
+        let rules =
+            rules(r#"[{"action": "allow"}, {"action": "disallow", "os": {"name": "osx"}}]"#);
+        assert_eq!(rules.is_allowed(&HashMap::new()), os_name() != "osx");
+    }
+
+    #[test]
+    fn only_osx() {
+        let rules = rules(r#"[{"action": "allow", "os": {"name": "osx"}}]"#);
+        assert_eq!(rules.is_allowed(&HashMap::new()), os_name() == "osx");
+    }
+
+    #[test]
+    fn nothing_allowed_without_a_match() {
+        let rules = rules(r#"[{"action": "disallow", "os": {"name": "osx"}}]"#);
+        assert!(!rules.is_allowed(&HashMap::new()));
+        assert!(!Rules(Vec::new()).is_allowed(&HashMap::new()));
+    }
+
+    #[test]
+    fn features_must_match() {
+        let rules = rules(r#"[{"action": "allow", "features": {"is_demo_user": true}}]"#);
+        assert!(!rules.is_allowed(&HashMap::new()));
+        assert!(!rules.is_allowed(&HashMap::from([("is_demo_user", false)])));
+        assert!(rules.is_allowed(&HashMap::from([("is_demo_user", true)])));
+    }
+
+    #[test]
+    fn fabric_child_inherits_vanilla() {
+        let mut child = version(FABRIC);
+        child.merge_with(&version(VANILLA));
+
+        assert_eq!(child.id, "fabric-loader-0.14.21-1.20.1");
+        assert_eq!(
+            child.main_class,
+            "net.fabricmc.loader.impl.launch.knot.KnotClient"
+        );
+        assert_eq!(child.inherits_from, None);
+        assert_eq!(child.assets.as_deref(), Some("5"));
+        assert_eq!(
+            child.asset_index.as_ref().map(|index| index.id.as_str()),
+            Some("5")
+        );
+        assert!(child.downloads.is_some());
+        assert_eq!(
+            child.java_version.as_ref().map(|java| java.major_version),
+            Some(17)
+        );
+        assert_eq!(child.compliance_level, Some(1));
+        assert_eq!(
+            library_names(&child),
+            [
+                "com.google.guava:guava:31.1-jre",
+                "com.mojang:brigadier:1.1.8",
+                "org.ow2.asm:asm:9.5",
+                "net.fabricmc:fabric-loader:0.14.21",
+            ]
+        );
+        let Arguments::Modern { game, jvm } = &child.arguments else {
+            panic!("arguments aren't modern");
+        };
+        assert_eq!(
+            plain(game),
+            [
+                "--username",
+                "${auth_player_name}",
+                "--version",
+                "${version_name}"
+            ]
+        );
+        assert_eq!(
+            plain(jvm),
+            [
+                "-Djava.library.path=${natives_directory}",
+                "-cp",
+                "${classpath}",
+                "-DFabricMcEmu= net.minecraft.client.main.Main ",
+            ]
+        );
+    }
+
+    #[test]
+    fn library_paths_from_coordinates() {
+        assert_eq!(
+            build_library_path("net.minecraftforge:forge:1.20.1-47.2.0:universal", None).as_deref(),
+            Some("net/minecraftforge/forge/1.20.1-47.2.0/forge-1.20.1-47.2.0-universal.jar")
+        );
+        assert_eq!(
+            build_library_path("net.minecraftforge:forge:1.20.1-47.2.0", Some("installer"))
+                .as_deref(),
+            Some("net/minecraftforge/forge/1.20.1-47.2.0/forge-1.20.1-47.2.0-installer.jar")
+        );
+        // Coordinate's own one wins
+        assert_eq!(
+            build_library_path(
+                "net.minecraftforge:forge:1.20.1-47.2.0:universal",
+                Some("installer")
+            )
+            .as_deref(),
+            Some("net/minecraftforge/forge/1.20.1-47.2.0/forge-1.20.1-47.2.0-universal.jar")
+        );
+        assert_eq!(
+            build_library_path("de.oceanlabs.mcp:mcp_config:1.20.1-20230612.114412@zip", None)
+                .as_deref(),
+            Some("de/oceanlabs/mcp/mcp_config/1.20.1-20230612.114412/mcp_config-1.20.1-20230612.114412.zip")
+        );
+        assert_eq!(build_library_path("net.minecraftforge:forge", None), None);
+    }
+
+    #[test]
+    fn maven_libraries_without_downloads() {
+        let child = version(FABRIC);
+        let paths: Vec<_> = child
+            .libraries
+            .iter()
+            .map(|lib| lib.artifact_path().unwrap())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "org/ow2/asm/asm/9.5/asm-9.5.jar",
+                "net/fabricmc/fabric-loader/0.14.21/fabric-loader-0.14.21.jar",
+            ]
+        );
+    }
+
+    fn fabric_with_guava(guava_version: &str) -> VersionInfo {
+        let mut child = version(FABRIC);
+        let guava = format!(
+            r#"{{"name": "com.google.guava:guava:{guava_version}", "url": "https://maven.fabricmc.net/"}}"#
+        );
+        child.libraries.push(serde_json::from_str(&guava).unwrap());
+        child
+    }
+
+    #[test]
+    fn newer_child_library_wins() {
+        let mut child = fabric_with_guava("32.1.2-jre");
+        child.merge_with(&version(VANILLA));
+        assert_eq!(
+            library_names(&child),
+            [
+                "com.mojang:brigadier:1.1.8",
+                "org.ow2.asm:asm:9.5",
+                "net.fabricmc:fabric-loader:0.14.21",
+                "com.google.guava:guava:32.1.2-jre",
+            ]
+        );
+    }
+
+    #[test]
+    fn older_child_library_is_shadowed() {
+        let mut child = fabric_with_guava("21.0");
+        child.merge_with(&version(VANILLA));
+        assert_eq!(
+            library_names(&child),
+            [
+                "com.google.guava:guava:31.1-jre",
+                "com.mojang:brigadier:1.1.8",
+                "org.ow2.asm:asm:9.5",
+                "net.fabricmc:fabric-loader:0.14.21",
+            ]
+        );
+    }
+
+    #[test]
+    fn replaced_arguments_fall_back_to_parent_ones() {
+        let mut child = version(FABRIC);
+        child.merge_with_strategy(&version(VANILLA), ArgumentMergeStrategy::Replace);
+        let Arguments::Modern { game, jvm } = &child.arguments else {
+            panic!("arguments aren't modern");
+        };
+        // Child has no game arguments of its own
+        assert_eq!(
+            plain(game),
+            [
+                "--username",
+                "${auth_player_name}",
+                "--version",
+                "${version_name}"
+            ]
+        );
+        assert_eq!(
+            plain(jvm),
+            ["-DFabricMcEmu= net.minecraft.client.main.Main "]
+        );
+    }
+
+    #[test]
+    fn legacy_parent_arguments_are_upgraded() {
+        let parent = version(include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/data/1.6.4.json"
+        )));
+        let mut child = version(FABRIC);
+        child.merge_with(&parent);
+        let Arguments::Modern { game, jvm } = &child.arguments else {
+            panic!("arguments aren't modern");
         };
-        self.other.as_ref().and_then(|other| other.get(native_str))
+        assert_eq!(
+            plain(game)[..4],
+            [
+                "--username",
+                "${auth_player_name}",
+                "--session",
+                "${auth_session}"
+            ]
+        );
+        assert_eq!(plain(game).last(), Some(&"${game_assets}"));
+        let jvm = plain(jvm);
+        assert!(jvm.contains(&"-cp"));
+        assert_eq!(
+            jvm.last(),
+            Some(&"-DFabricMcEmu= net.minecraft.client.main.Main ")
+        );
     }
 }
@@ -1,17 +1,19 @@
 use chrono::{DateTime, Utc};
+use serde::Deserializer;
 use serde_derive::Deserialize;
 use url::Url;
 
-#[derive(Deserialize, Debug)]
-#[serde(rename_all = "snake_case")]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ReleaseType {
     Release,
     Snapshot,
     OldAlpha,
     OldBeta,
+    // Types added later (e.g. experimental snapshots) shouldn't fail the whole manifest
+    Other(String),
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Version {
     pub id: String,
@@ -20,16 +22,144 @@ pub struct Version {
     pub url: Url,
     pub time: DateTime<Utc>,
     pub release_time: DateTime<Utc>,
+    pub sha1: Option<String>,
+    pub compliance_level: Option<usize>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Latest {
     pub release: String,
     pub snapshot: String,
 }
 
-#[derive(Deserialize, Debug)]
+// v1 lacks hashes and compliance levels of versions, both are parsed the same way
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestVersion {
+    V1,
+    V2,
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct VersionsManifest {
     pub latest: Latest,
     pub versions: Vec<Version>,
 }
+
+impl<'de> serde::Deserialize<'de> for ReleaseType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let release_type = String::deserialize(deserializer)?;
+        Ok(match release_type.as_str() {
+            "release" => Self::Release,
+            "snapshot" => Self::Snapshot,
+            "old_alpha" => Self::OldAlpha,
+            "old_beta" => Self::OldBeta,
+            _ => Self::Other(release_type),
+        })
+    }
+}
+
+impl ReleaseType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Release => "release",
+            Self::Snapshot => "snapshot",
+            Self::OldAlpha => "old_alpha",
+            Self::OldBeta => "old_beta",
+            Self::Other(release_type) => release_type,
+        }
+    }
+
+    pub fn is_known(&self) -> bool {
+        !matches!(self, Self::Other(_))
+    }
+}
+
+impl VersionsManifest {
+    // Every v2 version has a hash
+    pub fn schema(&self) -> ManifestVersion {
+        if self.versions.iter().any(|version| version.sha1.is_some()) {
+            ManifestVersion::V2
+        } else {
+            ManifestVersion::V1
+        }
+    }
+
+    pub fn version(&self, id: &str) -> Option<&Version> {
+        self.versions.iter().find(|version| version.id == id)
+    }
+
+    pub fn latest_release(&self) -> Option<&Version> {
+        self.version(&self.latest.release)
+    }
+
+    pub fn latest_snapshot(&self) -> Option<&Version> {
+        self.version(&self.latest.snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"{
+        "latest": {"release": "1.20.1", "snapshot": "23w31a"},
+        "versions": [
+            {
+                "id": "23w31a",
+                "type": "snapshot",
+                "url": "https://piston-meta.mojang.com/v1/packages/8d4ba6a1a7e0e8a5d3f0c2f6e9d1b8a0c7e6f5d4/23w31a.json",
+                "time": "2023-08-01T13:15:12+00:00",
+                "releaseTime": "2023-08-01T13:07:52+00:00",
+                "sha1": "8d4ba6a1a7e0e8a5d3f0c2f6e9d1b8a0c7e6f5d4",
+                "complianceLevel": 1
+            },
+            {
+                "id": "1.20.1-combat",
+                "type": "experiment",
+                "url": "https://example.com/1.20.1-combat.json",
+                "time": "2023-07-01T00:00:00+00:00",
+                "releaseTime": "2023-07-01T00:00:00+00:00"
+            },
+            {
+                "id": "1.20.1",
+                "type": "release",
+                "url": "https://piston-meta.mojang.com/v1/packages/b0cfe6e9d3c4e1a5f6a7b8c9d0e1f2a3b4c5d6e7/1.20.1.json",
+                "time": "2023-06-12T13:25:51+00:00",
+                "releaseTime": "2023-06-07T09:35:21+00:00",
+                "sha1": "b0cfe6e9d3c4e1a5f6a7b8c9d0e1f2a3b4c5d6e7",
+                "complianceLevel": 1
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn unknown_types_are_kept() {
+        let manifest: VersionsManifest = serde_json::from_str(MANIFEST).unwrap();
+        let experiment = manifest.version("1.20.1-combat").unwrap();
+        assert_eq!(
+            experiment.release_type,
+            ReleaseType::Other("experiment".to_owned())
+        );
+        assert_eq!(experiment.release_type.as_str(), "experiment");
+        assert!(!experiment.release_type.is_known());
+
+        let known: Vec<_> = manifest
+            .versions
+            .iter()
+            .filter(|version| version.release_type.is_known())
+            .map(|version| (version.id.as_str(), &version.release_type))
+            .collect();
+        assert_eq!(
+            known,
+            [
+                ("23w31a", &ReleaseType::Snapshot),
+                ("1.20.1", &ReleaseType::Release)
+            ]
+        );
+        assert_eq!(manifest.schema(), ManifestVersion::V2);
+        assert_eq!(
+            manifest.latest_release().map(|v| v.id.as_str()),
+            Some("1.20.1")
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use std::{
+    cmp::Ordering,
+    error::Error,
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+#[derive(Debug)]
+pub struct InvalidCoord(pub String);
+
+impl Display for InvalidCoord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid maven coordinate: {}", self.0)
+    }
+}
+
+impl Error for InvalidCoord {}
+
+// group:artifact:version[:classifier][@ext]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MavenCoord {
+    pub group: String,
+    pub artifact: String,
+    pub version: String,
+    pub classifier: Option<String>,
+    pub ext: String,
+}
+
+impl MavenCoord {
+    // Coordinate's own classifier takes precedence
+    pub fn or_classifier(self, classifier: Option<&str>) -> Self {
+        Self {
+            classifier: self.classifier.or_else(|| classifier.map(str::to_owned)),
+            ..self
+        }
+    }
+
+    // Same library regardless of version, i.e. group:artifact[:classifier] are equal
+    pub fn is_same_artifact(&self, other: &Self) -> bool {
+        self.group == other.group
+            && self.artifact == other.artifact
+            && self.classifier == other.classifier
+    }
+
+    // Numeric parts are compared as numbers, e.g. 31.1-jre > 17.0, others lexicographically
+    pub fn cmp_version(&self, other: &Self) -> Ordering {
+        let parts = |version: &str| {
+            version
+                .split(['.', '-', '_'])
+                .map(|part| part.parse::<u64>().map_err(|_| part.to_owned()))
+                .collect::<Vec<_>>()
+        };
+        let (ours, theirs) = (parts(&self.version), parts(&other.version));
+        for (a, b) in ours.iter().zip(&theirs) {
+            let ordering = match (a, b) {
+                (Ok(a), Ok(b)) => a.cmp(b),
+                (Ok(_), Err(_)) => Ordering::Greater,
+                (Err(_), Ok(_)) => Ordering::Less,
+                (Err(a), Err(b)) => a.cmp(b),
+            };
+            if ordering.is_ne() {
+                return ordering;
+            }
+        }
+        ours.len()
+            .cmp(&theirs.len())
+            .then_with(|| self.version.cmp(&other.version))
+    }
+
+    // group/artifact/version/artifact-version[-classifier].ext
+    pub fn to_path(&self) -> String {
+        let Self {
+            group,
+            artifact,
+            version,
+            classifier,
+            ext,
+        } = self;
+        let mut path = format!(
+            "{}/{artifact}/{version}/{artifact}-{version}",
+            group.replace('.', "/")
+        );
+        if let Some(classifier) = classifier {
+            path.push('-');
+            path.push_str(classifier);
+        }
+        path.push('.');
+        path.push_str(ext);
+        path
+    }
+}
+
+impl FromStr for MavenCoord {
+    type Err = InvalidCoord;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidCoord(s.to_owned());
+        let (coordinate, ext) = s.split_once('@').unwrap_or((s, "jar"));
+        let mut parts = coordinate.split(':');
+        let mut next = || parts.next().filter(|part| !part.is_empty());
+        let (group, artifact, version) = (
+            next().ok_or_else(invalid)?,
+            next().ok_or_else(invalid)?,
+            next().ok_or_else(invalid)?,
+        );
+        let classifier = next();
+        if next().is_some() || ext.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            group: group.to_owned(),
+            artifact: artifact.to_owned(),
+            version: version.to_owned(),
+            classifier: classifier.map(str::to_owned),
+            ext: ext.to_owned(),
+        })
+    }
+}
+
+impl Display for MavenCoord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.group, self.artifact, self.version)?;
+        if let Some(classifier) = &self.classifier {
+            write!(f, ":{classifier}")?;
+        }
+        if self.ext != "jar" {
+            write!(f, "@{}", self.ext)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coord(s: &str) -> MavenCoord {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_forge_coordinates() {
+        let forge = coord("net.minecraftforge:forge:1.20.1-47.2.0:universal");
+        assert_eq!(forge.group, "net.minecraftforge");
+        assert_eq!(forge.artifact, "forge");
+        assert_eq!(forge.version, "1.20.1-47.2.0");
+        assert_eq!(forge.classifier.as_deref(), Some("universal"));
+        assert_eq!(forge.ext, "jar");
+
+        let config = coord("de.oceanlabs.mcp:mcp_config:1.20.1-20230612.114412@zip");
+        assert_eq!(config.classifier, None);
+        assert_eq!(config.ext, "zip");
+    }
+
+    #[test]
+    fn rejects_malformed_coordinates() {
+        for s in [
+            "",
+            "net.minecraftforge:forge",
+            "net.minecraftforge::1.20.1",
+            "a:b:c:d:e",
+            "a:b:c@",
+        ] {
+            assert!(s.parse::<MavenCoord>().is_err(), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn displays_as_parsed() {
+        for s in [
+            "net.minecraftforge:forge:1.20.1-47.2.0:universal",
+            "de.oceanlabs.mcp:mcp_config:1.20.1-20230612.114412@zip",
+            "com.google.guava:guava:31.1-jre",
+        ] {
+            assert_eq!(coord(s).to_string(), s);
+        }
+    }
+
+    #[test]
+    fn compares_versions_numerically() {
+        let guava = |version: &str| coord(&format!("com.google.guava:guava:{version}"));
+        assert!(guava("31.1-jre").cmp_version(&guava("17.0")).is_gt());
+        assert!(guava("32.1.2-jre").cmp_version(&guava("31.1-jre")).is_gt());
+        assert!(guava("2.9.4-nightly-20150209")
+            .cmp_version(&guava("2.9.4"))
+            .is_gt());
+        assert!(guava("1.0").cmp_version(&guava("1.0")).is_eq());
+    }
+
+    #[test]
+    fn same_artifact_ignores_version_only() {
+        let forge = coord("net.minecraftforge:forge:1.20.1-47.2.0:universal");
+        assert!(forge.is_same_artifact(&coord("net.minecraftforge:forge:1.19.2-43.2.0:universal")));
+        assert!(!forge.is_same_artifact(&coord("net.minecraftforge:forge:1.20.1-47.2.0")));
+    }
+}
@@ -1,3 +1,4 @@
 pub mod assets;
 pub mod game;
 pub mod manifest;
+pub mod maven;
@@ -1,3 +1,5 @@
 pub static DEFAULT_RESOURCES_URL: &str = "http://resources.download.minecraft.net";
 pub static DEFAULT_MANIFEST_URL: &str =
     "https://piston-meta.mojang.com/mc/game/version_manifest_v2.json";
+// Versions aren't hashed there, i.e. ManifestVersion::V1
+pub static MANIFEST_V1_URL: &str = "https://piston-meta.mojang.com/mc/game/version_manifest.json";
@@ -1,18 +1,27 @@
 use std::{
     cell::UnsafeCell,
+    cmp::Ordering,
+    collections::BinaryHeap,
     fmt::{self, Debug},
     future::Future,
     mem::MaybeUninit,
     pin::Pin,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{self, AtomicBool, AtomicU64},
+        Arc, Mutex,
+    },
     task::{Context, Poll, Waker},
 };
 
 use crossbeam_utils::atomic::AtomicCell;
-use tokio::{sync::Semaphore, task::JoinSet};
+use tokio::{
+    sync::{oneshot, Notify},
+    task::{self, JoinSet},
+    time::{self, Duration, Instant},
+};
 use tracing::{info_span, instrument, trace, warn, Instrument};
 
-#[derive(Default, Debug, Copy, Clone)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u8)]
 pub enum State {
     #[default]
@@ -21,6 +30,21 @@ pub enum State {
     Paused,
     Cancelled,
     Finished,
+    Failed,
+}
+
+pub trait Fallible {
+    type Error;
+
+    fn error(&self) -> Option<&Self::Error>;
+}
+
+impl<T, E> Fallible for Result<T, E> {
+    type Error = E;
+
+    fn error(&self) -> Option<&Self::Error> {
+        self.as_ref().err()
+    }
 }
 
 struct Inner<M, R> {
@@ -72,7 +96,8 @@ impl<M, R> Handle<M, R> {
             trace!("waking up task");
             waker.wake();
         } else {
-            warn!("no waker");
+            // Not polled yet, i.e. waiting for a permit
+            trace!("no waker");
         }
     }
 
@@ -86,14 +111,16 @@ impl<M, R> Handle<M, R> {
 
     pub fn result(&self) -> Option<&R> {
         match self.state() {
-            // Safety: result must be initialized at moment when State eq Finished
-            State::Finished => Some(unsafe { (*self.inner.result.get()).assume_init_ref() }),
+            // Safety: result must be initialized at moment when State eq Finished or Failed
+            State::Finished | State::Failed => {
+                Some(unsafe { (*self.inner.result.get()).assume_init_ref() })
+            }
             _ => None,
         }
     }
 
     pub fn pause(&self) {
-        if matches!(self.state(), State::Running) {
+        if matches!(self.state(), State::Pending | State::Running) {
             self.change_state(State::Paused);
             self.wakeup();
         }
@@ -106,14 +133,98 @@ impl<M, R> Handle<M, R> {
         }
     }
 
+    // Cooperative point for long running tasks, yields while paused, false if cancelled
+    pub async fn checkpoint(&self) -> bool {
+        if matches!(self.state(), State::Paused) {
+            // Outer task won't poll again until resumed
+            task::yield_now().await;
+        }
+        !matches!(self.state(), State::Cancelled)
+    }
+
     pub fn cancel(&self) {
-        if matches!(self.state(), State::Running | State::Paused) {
+        if matches!(
+            self.state(),
+            State::Pending | State::Running | State::Paused
+        ) {
             self.change_state(State::Cancelled);
             self.wakeup();
         }
     }
 }
 
+impl<M, R: Fallible> Handle<M, R> {
+    pub fn error(&self) -> Option<&R::Error> {
+        match self.state() {
+            State::Failed => self.result().and_then(Fallible::error),
+            _ => None,
+        }
+    }
+}
+
+// Type erased control over a handle, so handles of different tasks can be kept together
+pub trait Control: Send + Sync {
+    fn state(&self) -> State;
+    fn pause(&self);
+    fn resume(&self);
+    fn cancel(&self);
+}
+
+impl<M: Send + Sync, R: Send + Sync> Control for Handle<M, R> {
+    fn state(&self) -> State {
+        Handle::state(self)
+    }
+
+    fn pause(&self) {
+        Handle::pause(self)
+    }
+
+    fn resume(&self) {
+        Handle::resume(self)
+    }
+
+    fn cancel(&self) {
+        Handle::cancel(self)
+    }
+}
+
+// Checked by blocking work between steps, e.g. extraction, which can't be stopped by dropping
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    // Cancelling the task cancels its blocking work too
+    control: Option<Arc<dyn Control>>,
+}
+
+impl Debug for CancelToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancelToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+impl CancelToken {
+    pub fn watching(control: Arc<dyn Control>) -> Self {
+        Self {
+            control: Some(control),
+            ..Default::default()
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, atomic::Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(atomic::Ordering::Relaxed)
+            || self
+                .control
+                .as_ref()
+                .is_some_and(|control| matches!(control.state(), State::Cancelled))
+    }
+}
+
 struct Task<M, R, F> {
     handle: Handle<M, R>,
     fut: F,
@@ -122,6 +233,7 @@ struct Task<M, R, F> {
 impl<M, R, F> Future for Task<M, R, F>
 where
     M: Debug,
+    R: Fallible,
     F: Future<Output = R> + Unpin,
 {
     type Output = ();
@@ -138,8 +250,13 @@ where
                     let fut = Pin::new(&mut this.fut);
                     match fut.poll(cx) {
                         Poll::Ready(res) => {
+                            let state = if res.error().is_some() {
+                                State::Failed
+                            } else {
+                                State::Finished
+                            };
                             this.handle.change_result(res);
-                            this.handle.change_state(State::Finished);
+                            this.handle.change_state(state);
                             continue;
                         }
                         Poll::Pending => {
@@ -152,7 +269,7 @@ where
                     this.handle.change_waker(cx.waker().clone());
                     return Poll::Pending;
                 }
-                State::Finished | State::Cancelled => {
+                State::Finished | State::Failed | State::Cancelled => {
                     return Poll::Ready(());
                 }
             }
@@ -165,12 +282,268 @@ pub trait GenerateTask: Sized {
     type Future: Future<Output = Self::Output> + Send + Unpin;
 
     fn task(handle: Handle<Self, Self::Output>) -> Self::Future;
+
+    // What's shown by Manager::snapshot, nothing unless the task reports it
+    fn name(&self) -> String {
+        String::new()
+    }
+
+    fn progress(&self) -> u64 {
+        0
+    }
+
+    fn size(&self) -> Option<u64> {
+        None
+    }
+
+    // Called on spawn by a Manager with a bandwidth limit, ignored unless the task transfers data
+    fn attach_rate_limiter(&mut self, _rate_limiter: &Arc<RateLimiter>) {}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskSnapshot {
+    pub name: String,
+    pub state: State,
+    pub progress: u64,
+    pub size: Option<u64>,
+}
+
+// Handle's control along with a reader of its metadata, so handles of any task are kept together
+struct Tracked {
+    control: Box<dyn Control>,
+    snapshot: Box<dyn Fn() -> TaskSnapshot + Send + Sync>,
+}
+
+// Time source for measurements, replaceable so they can be checked without waiting
+pub trait Clock: Debug + Send + Sync {
+    // Since an arbitrary, but fixed point
+    fn elapsed(&self) -> Duration;
+
+    // Until elapsed has moved on by at least the duration
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send + Sync + '_>> {
+        Box::pin(time::sleep(duration))
+    }
+}
+
+#[derive(Debug)]
+pub struct SystemClock(Instant);
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self(Instant::now())
+    }
+}
+
+impl Clock for SystemClock {
+    fn elapsed(&self) -> Duration {
+        self.0.elapsed()
+    }
+}
+
+// Advanced only by hand, which also wakes whoever sleeps on it
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    nanos: AtomicU64,
+    advanced: Notify,
+}
+
+impl ManualClock {
+    pub fn advance(&self, by: Duration) {
+        self.nanos
+            .fetch_add(by.as_nanos() as u64, atomic::Ordering::Relaxed);
+        self.advanced.notify_waiters();
+    }
+}
+
+impl Clock for ManualClock {
+    fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(atomic::Ordering::Relaxed))
+    }
+
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send + Sync + '_>> {
+        let until = self.elapsed() + duration;
+        Box::pin(async move {
+            loop {
+                // Registered before checking, so an advance in between isn't missed
+                let advanced = self.advanced.notified();
+                if self.elapsed() >= until {
+                    return;
+                }
+                advanced.await;
+            }
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    // Negative when a chunk bigger than available tokens was taken
+    tokens: f64,
+    last_refill: Instant,
+}
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec,
+            bucket: Mutex::new(Bucket {
+                tokens: bytes_per_sec as f64,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec
+    }
+
+    fn refill(&self, bucket: &mut Bucket) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        let capacity = self.bytes_per_sec as f64;
+        bucket.tokens = (bucket.tokens + elapsed * capacity).min(capacity);
+        bucket.last_refill = now;
+    }
+
+    // Tokens are reserved before waiting, so tasks are served in order and none is starved.
+    // Lock isn't held across awaits, so a paused task doesn't block the others.
+    pub async fn acquire(&self, bytes: u64) {
+        // Zero means there's no limit
+        if self.bytes_per_sec == 0 {
+            return;
+        }
+        let deficit = {
+            let mut bucket = self.bucket.lock().unwrap();
+            self.refill(&mut bucket);
+            bucket.tokens -= bytes as f64;
+            -bucket.tokens / self.bytes_per_sec as f64
+        };
+        if deficit > 0.0 {
+            trace!(deficit, "waiting for bandwidth");
+            time::sleep(Duration::from_secs_f64(deficit)).await;
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Waiter {
+    priority: u64,
+    seq: u64,
+    tx: oneshot::Sender<()>,
+}
+
+impl Waiter {
+    fn key(&self) -> (u64, u64) {
+        (self.priority, self.seq)
+    }
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Waiter {}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Reversed, so the max-heap pops the lowest priority, then the earliest one
+impl Ord for Waiter {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.key().cmp(&self.key())
+    }
+}
+
+#[derive(Debug)]
+struct Queue {
+    running: usize,
+    next_seq: u64,
+    waiters: BinaryHeap<Waiter>,
+}
+
+// Semaphore handing out permits by priority instead of FIFO
+#[derive(Debug)]
+struct Scheduler {
+    limit: usize,
+    queue: Mutex<Queue>,
+}
+
+#[derive(Debug)]
+struct Permit(Arc<Scheduler>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+impl Scheduler {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            queue: Mutex::new(Queue {
+                running: 0,
+                next_seq: 0,
+                waiters: BinaryHeap::new(),
+            }),
+        }
+    }
+
+    fn available_permits(&self) -> usize {
+        let queue = self.queue.lock().unwrap();
+        self.limit.saturating_sub(queue.running)
+    }
+
+    async fn acquire(self: Arc<Self>, priority: u64) -> Permit {
+        let rx = {
+            let mut queue = self.queue.lock().unwrap();
+            if queue.running < self.limit && queue.waiters.is_empty() {
+                queue.running += 1;
+                None
+            } else {
+                let (tx, rx) = oneshot::channel();
+                let seq = queue.next_seq;
+                queue.next_seq += 1;
+                queue.waiters.push(Waiter { priority, seq, tx });
+                Some(rx)
+            }
+        };
+        if let Some(rx) = rx {
+            // Permit is moved to the waiter by the releasing one
+            rx.await.expect("scheduler dropped");
+        }
+        Permit(self)
+    }
+
+    fn release(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        while let Some(Waiter { tx, .. }) = queue.waiters.pop() {
+            // Receiver is gone when its task was aborted
+            if tx.send(()).is_ok() {
+                return;
+            }
+        }
+        queue.running -= 1;
+    }
 }
 
 #[derive(Default)]
 pub struct Manager {
-    semaphore: Option<Arc<Semaphore>>,
+    scheduler: Option<Arc<Scheduler>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
     tasks: JoinSet<()>,
+    handles: Vec<Tracked>,
 }
 
 impl Debug for Manager {
@@ -178,6 +551,7 @@ impl Debug for Manager {
         f.debug_struct("Manager")
             .field("tasks", &self.tasks())
             .field("permits", &self.permits())
+            .field("rate_limiter", &self.rate_limiter)
             .finish()
     }
 }
@@ -185,25 +559,69 @@ impl Debug for Manager {
 impl Manager {
     pub fn with_limit(self, limit: usize) -> Self {
         Self {
-            semaphore: Some(Arc::new(Semaphore::new(limit))),
+            scheduler: Some(Arc::new(Scheduler::new(limit))),
+            ..self
+        }
+    }
+
+    // Shared by every task spawned later, zero removes the limit
+    pub fn with_bandwidth_limit(self, bytes_per_sec: u64) -> Self {
+        Self {
+            rate_limiter: (bytes_per_sec > 0).then(|| Arc::new(RateLimiter::new(bytes_per_sec))),
             ..self
         }
     }
 
+    pub fn rate_limiter(&self) -> Option<&Arc<RateLimiter>> {
+        self.rate_limiter.as_ref()
+    }
+
     pub fn tasks(&self) -> usize {
         self.tasks.len()
     }
 
     pub fn permits(&self) -> Option<usize> {
-        self.semaphore.as_ref().map(|sem| sem.available_permits())
+        self.scheduler
+            .as_ref()
+            .map(|scheduler| scheduler.available_permits())
+    }
+
+    pub fn pause_all(&self) {
+        self.handles
+            .iter()
+            .for_each(|tracked| tracked.control.pause());
+    }
+
+    pub fn resume_all(&self) {
+        self.handles
+            .iter()
+            .for_each(|tracked| tracked.control.resume());
+    }
+
+    pub fn cancel_all(&self) {
+        self.handles
+            .iter()
+            .for_each(|tracked| tracked.control.cancel());
     }
 
-    #[instrument]
     pub fn new_task<M, R>(&mut self, metadata: M) -> Handle<M, R>
     where
-        R: Send + Sync + 'static,
+        R: Fallible + Send + Sync + 'static,
         M: GenerateTask<Output = R> + Debug + Send + Sync + 'static,
     {
+        self.new_task_with_priority(metadata, 0)
+    }
+
+    // Lower priority is started first when the limit is reached, equal ones are FIFO
+    #[instrument]
+    pub fn new_task_with_priority<M, R>(&mut self, mut metadata: M, priority: u64) -> Handle<M, R>
+    where
+        R: Fallible + Send + Sync + 'static,
+        M: GenerateTask<Output = R> + Debug + Send + Sync + 'static,
+    {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            metadata.attach_rate_limiter(rate_limiter);
+        }
         let handle = Handle {
             inner: Arc::new(Inner {
                 metadata,
@@ -216,14 +634,12 @@ impl Manager {
             handle: handle.clone(),
             fut: M::task(handle.clone()),
         };
-        let semaphore = self.semaphore.clone();
+        let scheduler = self.scheduler.clone();
         self.tasks.spawn(
             async move {
                 trace!("trying to acquire permit");
-                let _permit = match semaphore {
-                    Some(semaphore) => {
-                        Some(semaphore.acquire_owned().await.expect("semaphore closed"))
-                    }
+                let _permit = match scheduler {
+                    Some(scheduler) => Some(scheduler.acquire(priority).await),
                     _ => None,
                 };
                 trace!("permit acquired");
@@ -231,12 +647,66 @@ impl Manager {
             }
             .instrument(info_span!("task_execute")),
         );
+        // Exited tasks' handles aren't needed anymore
+        self.handles.retain(|tracked| {
+            !matches!(
+                tracked.control.state(),
+                State::Finished | State::Failed | State::Cancelled
+            )
+        });
+        let tracked = handle.clone();
+        self.handles.push(Tracked {
+            control: Box::new(handle.clone()),
+            snapshot: Box::new(move || {
+                let metadata = tracked.metadata();
+                TaskSnapshot {
+                    name: metadata.name(),
+                    state: tracked.state(),
+                    progress: metadata.progress(),
+                    size: metadata.size(),
+                }
+            }),
+        });
 
         handle
     }
 
+    // Every tracked task in one pass, i.e. once per frame instead of polling each handle.
+    // Exited tasks are kept until the next one is spawned
+    pub fn snapshot(&self) -> Vec<TaskSnapshot> {
+        self.handles
+            .iter()
+            .map(|tracked| (tracked.snapshot)())
+            .collect()
+    }
+
+    #[instrument]
+    pub async fn wait_next(&mut self) -> bool {
+        self.tasks.join_next().await.is_some()
+    }
+
     #[instrument]
     pub async fn wait_all(&mut self) {
         while self.tasks.join_next().await.is_some() {}
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn manual_sleep_ends_once_advanced() {
+        let clock = ManualClock::default();
+        let mut sleeping = clock.sleep(Duration::from_secs(2));
+        clock.advance(Duration::from_secs(1));
+        assert!(time::timeout(Duration::from_millis(50), &mut sleeping)
+            .await
+            .is_err());
+
+        clock.advance(Duration::from_secs(1));
+        time::timeout(Duration::from_secs(1), sleeping)
+            .await
+            .expect("sleep isn't woken");
+    }
+}
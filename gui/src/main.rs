@@ -36,7 +36,7 @@ impl eframe::App for MyApp {
                 .show(ui);
             egui::ComboBox::from_label("Version")
                 .selected_text(&self.main.version)
-                .show_ui(ui, |ui| {});
+                .show_ui(ui, |_ui| {});
             if ui.button("Run game").clicked() {
                 // TODO : run and go to log
             }